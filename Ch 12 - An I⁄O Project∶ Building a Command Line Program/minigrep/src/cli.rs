@@ -0,0 +1,356 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+/// When to colour output with ANSI escape sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ColorChoice {
+    /// Only when writing to a terminal.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// How to order the lines printed for each file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SortBy {
+    /// Closest matches first, by edit distance (with --fuzzy).
+    Score,
+}
+
+/// Options that make no sense when matching fields of records, since
+/// records have no context and their matches are not located.
+const STRUCTURED_CONFLICTS: [&str; 6] = [
+    "context",
+    "before_context",
+    "after_context",
+    "replace",
+    "sort",
+    "interactive",
+];
+
+/// Search for QUERY in each PATH and print the lines that contain it.
+///
+/// With `-e` or `-f`, QUERY is not given and every positional argument is a
+/// PATH; lines matching any of the patterns are printed.
+///
+/// With `--interactive`, every positional argument is a PATH too, and the
+/// query is typed into a terminal UI that shows results as you type.
+/// Enter opens the selected line in $VISUAL or $EDITOR, and Esc quits.
+///
+/// Directories are searched recursively. With no PATH, or when PATH is `-`,
+/// standard input is searched.
+///
+/// Case sensitivity: `-i`, `--no-ignore-case` and `--smart-case` win over
+/// each other in the order they are given; without any of them, setting
+/// IGNORE_CASE in the environment turns on case-insensitive matching.
+///
+/// The exit status is 0 if a line was selected (with `-L`, if a file was
+/// listed), 1 if not, and 2 if there was an error, unless `-q` found a
+/// match anyway.
+///
+/// With `--follow`, files are followed as they grow, like `tail -F`, until
+/// minigrep is interrupted. Only what is written after minigrep starts is
+/// searched, unless `--from-start` is given too. Directories cannot be
+/// followed.
+///
+/// `minigrep index build DIR` indexes the trigrams of the files in DIR, so
+/// that later searches in DIR with `--index` can skip the files that
+/// cannot match. Run it again to update the index; only files that changed
+/// are read. To search for the word "index" itself, use `-e index`.
+///
+/// Colours can be changed with MINIGREP_COLORS, which takes the same
+/// format as grep's GREP_COLORS, e.g. `mt=01;31:fn=35:ln=32:bn=32:se=36`.
+#[derive(Debug, Parser)]
+#[command(
+    name = "minigrep",
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// Search for PATTERN; may be given more than once
+    #[arg(short = 'e', long = "regexp", value_name = "PATTERN")]
+    pub patterns: Vec<String>,
+
+    /// Read patterns from FILE, one per line
+    #[arg(short = 'f', long = "file", value_name = "FILE")]
+    pub pattern_files: Vec<PathBuf>,
+
+    /// Treat QUERY as a regular expression
+    #[arg(short = 'E', long = "regex", overrides_with = "fixed_strings")]
+    pub regex: bool,
+
+    /// Treat QUERY as a literal string (the default)
+    #[arg(short = 'F', long = "fixed-strings", overrides_with = "regex")]
+    pub fixed_strings: bool,
+
+    /// Ignore case distinctions
+    #[arg(short, long, overrides_with_all = ["no_ignore_case", "smart_case"])]
+    pub ignore_case: bool,
+
+    /// Match case exactly, even if IGNORE_CASE is set
+    #[arg(long, overrides_with_all = ["ignore_case", "smart_case"])]
+    pub no_ignore_case: bool,
+
+    /// Ignore case unless QUERY contains an uppercase letter
+    #[arg(short = 'S', long, overrides_with_all = ["ignore_case", "no_ignore_case"])]
+    pub smart_case: bool,
+
+    /// Match lines containing QUERY with at most K typos (insertions,
+    /// deletions or substitutions)
+    #[arg(
+        long,
+        value_name = "K",
+        conflicts_with_all = ["regex", "word_regexp", "line_regexp"]
+    )]
+    pub fuzzy: Option<u32>,
+
+    /// Order the matching lines of each file
+    #[arg(
+        long,
+        value_enum,
+        value_name = "KEY",
+        requires = "fuzzy",
+        conflicts_with_all = ["context", "before_context", "after_context", "invert_match"]
+    )]
+    pub sort: Option<SortBy>,
+
+    /// Select lines that do not match
+    #[arg(short = 'v', long)]
+    pub invert_match: bool,
+
+    /// Prefix each line with its line number
+    #[arg(short = 'n', long)]
+    pub line_number: bool,
+
+    /// Prefix each line with its byte offset in the input
+    #[arg(short = 'b', long)]
+    pub byte_offset: bool,
+
+    /// Print N lines of context after each match
+    #[arg(short = 'A', long, value_name = "N")]
+    pub after_context: Option<usize>,
+
+    /// Print N lines of context before each match
+    #[arg(short = 'B', long, value_name = "N")]
+    pub before_context: Option<usize>,
+
+    /// Print N lines of context around each match
+    #[arg(short = 'C', long, value_name = "N")]
+    pub context: Option<usize>,
+
+    /// Print only the number of selected lines
+    #[arg(short, long)]
+    pub count: bool,
+
+    /// Print only the name of the file if it has a selected line
+    #[arg(short = 'l', long, overrides_with = "files_without_match")]
+    pub files_with_matches: bool,
+
+    /// Print only the name of the file if it has no selected line
+    #[arg(short = 'L', long, overrides_with = "files_with_matches")]
+    pub files_without_match: bool,
+
+    /// Print nothing, and stop at the first match; only the exit status
+    /// tells whether anything matched
+    #[arg(short, long, visible_alias = "silent")]
+    pub quiet: bool,
+
+    /// Only match whole words
+    #[arg(short, long)]
+    pub word_regexp: bool,
+
+    /// Only match whole lines
+    #[arg(short = 'x', long)]
+    pub line_regexp: bool,
+
+    /// Colour matches, file names, line numbers and separators
+    #[arg(
+        long,
+        value_enum,
+        value_name = "WHEN",
+        num_args = 0..=1,
+        default_value_t = ColorChoice::Auto,
+        default_missing_value = "auto"
+    )]
+    pub color: ColorChoice,
+
+    /// Print results as JSON Lines, one event per line
+    #[arg(
+        long,
+        conflicts_with_all = ["count", "files_with_matches", "files_without_match", "quiet"]
+    )]
+    pub json: bool,
+
+    /// Print matching lines with each match replaced by TEMPLATE; in regex
+    /// mode, `$1` or `${name}` insert capture groups and `$$` a literal `$`
+    #[arg(long, value_name = "TEMPLATE")]
+    pub replace: Option<String>,
+
+    /// Rewrite the files with the replacements instead of printing them
+    #[arg(
+        long,
+        requires = "replace",
+        conflicts_with_all = [
+            "invert_match",
+            "count",
+            "files_with_matches",
+            "files_without_match",
+            "quiet",
+            "json"
+        ]
+    )]
+    pub in_place: bool,
+
+    /// Show a diff of what --in-place would change without writing anything
+    #[arg(long, requires = "in_place")]
+    pub dry_run: bool,
+
+    /// Print the file name for each match (the default with several files)
+    #[arg(short = 'H', long, overrides_with = "no_filename")]
+    pub with_filename: bool,
+
+    /// Never print file names
+    #[arg(long, overrides_with = "with_filename")]
+    pub no_filename: bool,
+
+    /// Search only files whose name matches GLOB
+    #[arg(long, value_name = "GLOB")]
+    pub include: Vec<String>,
+
+    /// Skip files whose name matches GLOB
+    #[arg(long, value_name = "GLOB")]
+    pub exclude: Vec<String>,
+
+    /// Search hidden files and directories
+    #[arg(long)]
+    pub hidden: bool,
+
+    /// Don't respect .gitignore and .ignore files
+    #[arg(long)]
+    pub no_ignore: bool,
+
+    /// Search binary files as if they were text
+    #[arg(short = 'a', long)]
+    pub text: bool,
+
+    /// Treat input as CSV with a header row, match only the column NAME,
+    /// and print whole records
+    #[arg(
+        long,
+        value_name = "NAME",
+        conflicts_with_all = STRUCTURED_CONFLICTS,
+        conflicts_with = "json_path"
+    )]
+    pub csv_column: Option<String>,
+
+    /// Treat input as JSON Lines, match only the value at EXPR (such as
+    /// `.user.name` or `.items[0]`), and print whole lines
+    #[arg(long, value_name = "EXPR", conflicts_with_all = STRUCTURED_CONFLICTS)]
+    pub json_path: Option<String>,
+
+    /// Search inside files compressed with gzip, bzip2, xz or zstd,
+    /// recognised by their first bytes
+    #[arg(short = 'z', long, conflicts_with = "in_place")]
+    pub search_zip: bool,
+
+    /// Memory-map large files rather than reading them, which saves copying
+    /// them but kills minigrep if a file is truncated while it is searched
+    #[arg(long)]
+    pub mmap: bool,
+
+    /// Skip files that the index of each directory searched, or of the
+    /// nearest directory above it that has one, rules out; see
+    /// `minigrep index build`
+    #[arg(
+        long,
+        conflicts_with_all = ["fuzzy", "csv_column", "json_path", "search_zip", "interactive"]
+    )]
+    pub index: bool,
+
+    /// Keep each file open at its end and search lines as they are
+    /// appended, starting again from the top when the file is truncated or
+    /// replaced, as when logs are rotated. Line numbers and byte offsets
+    /// count from where following began
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "count",
+            "files_with_matches",
+            "files_without_match",
+            "quiet",
+            "sort",
+            "in_place",
+            "interactive",
+            "search_zip",
+            "csv_column",
+            "index"
+        ]
+    )]
+    pub follow: bool,
+
+    /// With --follow, search each file from the start before following it
+    #[arg(long, requires = "follow")]
+    pub from_start: bool,
+
+    /// Number of files to search at once [default: number of CPUs]
+    #[arg(short = 'j', long, value_name = "N", value_parser = clap::value_parser!(u16).range(1..))]
+    pub threads: Option<u16>,
+
+    /// When searching several files at once, print each as soon as it is
+    /// done rather than in the order they were found
+    #[arg(long)]
+    pub unordered: bool,
+
+    /// Type the query into a terminal UI that searches as you type
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "patterns",
+            "pattern_files",
+            "count",
+            "files_with_matches",
+            "files_without_match",
+            "quiet",
+            "json",
+            "in_place",
+            "replace",
+            "sort",
+            "csv_column",
+            "json_path"
+        ]
+    )]
+    pub interactive: bool,
+
+    /// The string or pattern to search for
+    #[arg(required_unless_present_any = ["patterns", "pattern_files", "interactive"])]
+    pub query: Option<String>,
+
+    /// The files or directories to search
+    #[arg(value_name = "PATH")]
+    pub paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Manage the trigram index that --index uses
+    Index {
+        #[command(subcommand)]
+        command: IndexCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum IndexCommand {
+    /// Build or update the index of DIR, in `.minigrep-index` in DIR. Only
+    /// files that are new or changed, going by their size and modification
+    /// time, are read
+    Build {
+        /// The directory to index
+        #[arg(value_name = "DIR", default_value = ".")]
+        dir: PathBuf,
+    },
+}
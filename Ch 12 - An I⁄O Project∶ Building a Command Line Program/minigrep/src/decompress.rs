@@ -0,0 +1,159 @@
+use bzip2::bufread::MultiBzDecoder;
+use flate2::bufread::MultiGzDecoder;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+use xz2::bufread::XzDecoder;
+
+/// The length of the longest magic number `Format::detect` looks for.
+const MAX_MAGIC_LEN: usize = 6;
+
+/// A compression format that `-z` can see through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gzip,
+    Bzip2,
+    Xz,
+    Zstd,
+}
+
+impl Format {
+    /// The format of the file at `path`, going by the magic bytes at the
+    /// start of `header`. Only when `header` is too short to tell is the
+    /// extension of `path` trusted instead, since names can lie: a plain
+    /// text file called `notes.gz` is searched as it is.
+    pub fn detect(path: &Path, header: &[u8]) -> Option<Format> {
+        if let Some(format) = Format::from_magic(header) {
+            return Some(format);
+        }
+        if header.len() >= MAX_MAGIC_LEN {
+            return None;
+        }
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("gz" | "tgz") => Some(Format::Gzip),
+            Some("bz2" | "tbz2") => Some(Format::Bzip2),
+            Some("xz" | "txz") => Some(Format::Xz),
+            Some("zst") => Some(Format::Zstd),
+            _ => None,
+        }
+    }
+
+    fn from_magic(header: &[u8]) -> Option<Format> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Format::Gzip)
+        } else if header.starts_with(b"BZh") {
+            Some(Format::Bzip2)
+        } else if header.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0]) {
+            Some(Format::Xz)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Format::Zstd)
+        } else {
+            None
+        }
+    }
+}
+
+/// Wrap `reader` so that it yields the decompressed contents of `path` if
+/// it is compressed, or the contents as they are otherwise. Decompression
+/// happens as the returned reader is read, so the whole file never has to
+/// fit in memory.
+pub fn reader<'a>(path: &Path, mut reader: impl BufRead + 'a) -> io::Result<Box<dyn BufRead + 'a>> {
+    Ok(match Format::detect(path, reader.fill_buf()?) {
+        // Rotated logs are often several streams concatenated, so keep
+        // decoding after the first one ends.
+        Some(Format::Gzip) => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Some(Format::Bzip2) => Box::new(BufReader::new(MultiBzDecoder::new(reader))),
+        Some(Format::Xz) => Box::new(BufReader::new(XzDecoder::new_multi_decoder(reader))),
+        Some(Format::Zstd) => Box::new(BufReader::new(zstd::Decoder::with_buffer(reader)?)),
+        None => Box::new(reader),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    const TEXT: &str = "one\ntwo\nthree\n";
+
+    fn compress(format: Format) -> Vec<u8> {
+        let mut out = Vec::new();
+        match format {
+            Format::Gzip => {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(&mut out, flate2::Compression::default());
+                encoder.write_all(TEXT.as_bytes()).unwrap();
+                encoder.finish().unwrap();
+            }
+            Format::Bzip2 => {
+                let mut encoder =
+                    bzip2::write::BzEncoder::new(&mut out, bzip2::Compression::default());
+                encoder.write_all(TEXT.as_bytes()).unwrap();
+                encoder.finish().unwrap();
+            }
+            Format::Xz => {
+                let mut encoder = xz2::write::XzEncoder::new(&mut out, 6);
+                encoder.write_all(TEXT.as_bytes()).unwrap();
+                encoder.finish().unwrap();
+            }
+            Format::Zstd => out = zstd::encode_all(TEXT.as_bytes(), 0).unwrap(),
+        }
+        out
+    }
+
+    fn decompress(path: &str, data: &[u8]) -> String {
+        let mut text = String::new();
+        reader(Path::new(path), data)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    }
+
+    #[test]
+    fn detects_formats_by_magic_bytes_then_extension() {
+        assert_eq!(
+            Some(Format::Bzip2),
+            Format::detect(Path::new("app.log.1"), b"BZh91AY")
+        );
+        assert_eq!(
+            Some(Format::Zstd),
+            Format::detect(Path::new("app.log.gz"), &compress(Format::Zstd))
+        );
+        assert_eq!(None, Format::detect(Path::new("notes.gz"), b"plain text"));
+
+        // Too short to tell, so the extension decides.
+        assert_eq!(
+            Some(Format::Zstd),
+            Format::detect(Path::new("app.log.zst"), b"")
+        );
+        assert_eq!(None, Format::detect(Path::new("app.log"), b"BZ"));
+    }
+
+    #[test]
+    fn decompresses_every_format() {
+        for (format, name) in [
+            (Format::Gzip, "app.log.gz"),
+            (Format::Bzip2, "app.log.bz2"),
+            (Format::Xz, "app.log.xz"),
+            (Format::Zstd, "app.log.zst"),
+        ] {
+            let data = compress(format);
+            assert_eq!(TEXT, decompress(name, &data), "{format:?}");
+            assert_eq!(TEXT, decompress("-", &data), "{format:?} on stdin");
+        }
+    }
+
+    #[test]
+    fn reads_concatenated_gzip_streams() {
+        let mut data = compress(Format::Gzip);
+        data.extend(compress(Format::Gzip));
+
+        assert_eq!(TEXT.repeat(2), decompress("app.log.gz", &data));
+    }
+
+    #[test]
+    fn passes_plain_text_through() {
+        assert_eq!(TEXT, decompress("app.log", TEXT.as_bytes()));
+    }
+}
@@ -0,0 +1,307 @@
+//! `--follow`: searching files as they grow, like `tail -F`.
+//!
+//! A [`Follower`] reads a file like any other reader, except that at the
+//! end it waits for more to be written instead of stopping. That makes it
+//! an input the searcher can search as usual, context and all; the search
+//! just never finishes. Followers poll rather than waiting on file system
+//! events, which works the same everywhere and for any file system.
+
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait at the end of a file before looking again.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Reads the file at a path and waits at its end for more.
+///
+/// Like `tail -f`, it starts at the end of the file unless asked to read
+/// what is there already.
+///
+/// When the file shrinks, it has been truncated, and reading starts again
+/// from the top. When a different file appears at the path, the old one
+/// has been rotated away, and reading carries on with the new one once
+/// the old one has been read to the end. Either way, the searcher sees one
+/// long input, so line numbers and byte offsets keep counting up.
+pub struct Follower<'a> {
+    path: PathBuf,
+    file: File,
+    /// How much of `file` has been read.
+    position: u64,
+    /// Set to make reads return end of file, so that searches finish.
+    stop: &'a AtomicBool,
+}
+
+impl<'a> Follower<'a> {
+    pub fn open(path: &Path, from_start: bool, stop: &'a AtomicBool) -> io::Result<Follower<'a>> {
+        let mut file = File::open(path)?;
+        let position = if from_start {
+            0
+        } else {
+            file.seek(SeekFrom::End(0))?
+        };
+
+        Ok(Follower {
+            path: path.to_path_buf(),
+            file,
+            position,
+            stop,
+        })
+    }
+
+    /// At the end of the file, start again from the top if it was
+    /// truncated or replaced, and return whether it was.
+    fn reopen(&mut self) -> io::Result<bool> {
+        let open = self.file.metadata()?;
+        if open.len() < self.position {
+            eprintln!("minigrep: {}: file truncated", self.path.display());
+            self.file.seek(SeekFrom::Start(0))?;
+            self.position = 0;
+            return Ok(true);
+        }
+
+        // While nothing is at the path, the old file may still be written
+        // to, so keep following that.
+        let replaced = fs::metadata(&self.path).is_ok_and(|at_path| !same_file(&open, &at_path));
+        if !replaced {
+            return Ok(false);
+        }
+        let Ok(file) = File::open(&self.path) else {
+            return Ok(false);
+        };
+
+        eprintln!(
+            "minigrep: {}: file replaced; following the new file",
+            self.path.display()
+        );
+        self.file = file;
+        self.position = 0;
+        Ok(true)
+    }
+}
+
+impl Read for Follower<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.stop.load(Ordering::Relaxed) {
+                return Ok(0);
+            }
+
+            let read = self.file.read(buf)?;
+            if read > 0 {
+                self.position += read as u64;
+                return Ok(read);
+            }
+
+            if !self.reopen()? {
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+/// Without inode numbers there is no cheap way to tell, so elsewhere only
+/// truncation is noticed.
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    true
+}
+
+/// What a search sends back to the thread doing the writing.
+pub enum Output {
+    /// One or more whole lines of output.
+    Lines(Vec<u8>),
+    /// The search has ended, having selected this many lines.
+    Done(Result<u64, String>),
+}
+
+/// Sends output on a line at a time, so that lines from files followed at
+/// the same time are never mixed up.
+pub struct LineSender {
+    buffer: Vec<u8>,
+    sender: mpsc::Sender<Output>,
+}
+
+impl Write for LineSender {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+
+        if let Some(end) = self.buffer.iter().rposition(|&b| b == b'\n') {
+            let rest = self.buffer.split_off(end + 1);
+            let lines = mem::replace(&mut self.buffer, rest);
+            self.sender
+                .send(Output::Lines(lines))
+                .map_err(|_| io::Error::from(io::ErrorKind::BrokenPipe))?;
+        }
+
+        Ok(buf.len())
+    }
+
+    /// Half a line is held back until the rest of it is written.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Search each of `files` on a thread of its own, since following one
+/// never finishes. `search` is given a flag to hand to its `Follower` and
+/// somewhere to write; `emit` gets what is written a line at a time, and
+/// then the result of each search. If `emit` fails, every follower is
+/// stopped and the error returned once the searches have finished.
+pub fn search_files<I, S, E>(files: I, search: S, mut emit: E) -> io::Result<()>
+where
+    I: Iterator<Item = Result<PathBuf, String>>,
+    S: Fn(&Path, &AtomicBool, &mut LineSender) -> Result<u64, String> + Sync,
+    E: FnMut(Output) -> io::Result<()>,
+{
+    let stop = AtomicBool::new(false);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for file in files {
+            let mut out = LineSender {
+                buffer: Vec::new(),
+                sender: sender.clone(),
+            };
+            let (search, stop) = (&search, &stop);
+
+            scope.spawn(move || {
+                let result = file.and_then(|path| search(&path, stop, &mut out));
+                let _ = out.sender.send(Output::Done(result));
+            });
+        }
+
+        // Only the searches hold senders now, so this ends once they have
+        // all finished.
+        drop(sender);
+
+        for output in &receiver {
+            if let Err(e) = emit(output) {
+                stop.store(true, Ordering::Relaxed);
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+    use tempfile::TempDir;
+
+    fn file(dir: &TempDir, name: &str, contents: &str) -> PathBuf {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    fn append(path: &Path, contents: &str) {
+        let mut file = fs::OpenOptions::new().append(true).open(path).unwrap();
+        file.write_all(contents.as_bytes()).unwrap();
+    }
+
+    fn next_line(reader: &mut impl BufRead) -> String {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        line
+    }
+
+    #[test]
+    fn waits_for_lines_to_be_appended() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = file(&dir, "append", "one\n");
+        let stop = AtomicBool::new(false);
+        let mut reader = BufReader::new(Follower::open(&path, true, &stop).unwrap());
+        assert_eq!("one\n", next_line(&mut reader));
+
+        let writer = thread::spawn({
+            let path = path.clone();
+            move || {
+                thread::sleep(Duration::from_millis(50));
+                append(&path, "two\n");
+            }
+        });
+        assert_eq!("two\n", next_line(&mut reader));
+        writer.join().unwrap();
+
+        stop.store(true, Ordering::Relaxed);
+        assert_eq!("", next_line(&mut reader));
+    }
+
+    #[test]
+    fn starts_at_the_end_like_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = file(&dir, "end", "old\n");
+        let stop = AtomicBool::new(false);
+        let mut reader = BufReader::new(Follower::open(&path, false, &stop).unwrap());
+
+        append(&path, "new\n");
+        assert_eq!("new\n", next_line(&mut reader));
+    }
+
+    #[test]
+    fn starts_again_when_truncated() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = file(&dir, "truncate", "one\ntwo\n");
+        let stop = AtomicBool::new(false);
+        let mut reader = BufReader::new(Follower::open(&path, true, &stop).unwrap());
+        assert_eq!("one\n", next_line(&mut reader));
+        assert_eq!("two\n", next_line(&mut reader));
+
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!("new\n", next_line(&mut reader));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn follows_a_file_that_replaces_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = file(&dir, "rotate", "old\n");
+        let stop = AtomicBool::new(false);
+        let mut reader = BufReader::new(Follower::open(&path, false, &stop).unwrap());
+
+        // Longer than the old file, so it cannot pass for a truncation.
+        // The new file is read from the start.
+        let new = file(&dir, "rotate.new", "the new file\n");
+        fs::rename(&new, &path).unwrap();
+        assert_eq!("the new file\n", next_line(&mut reader));
+    }
+
+    #[test]
+    fn sends_whole_lines() {
+        let (sender, receiver) = mpsc::channel();
+        let mut out = LineSender {
+            buffer: Vec::new(),
+            sender,
+        };
+
+        out.write_all(b"one").unwrap();
+        out.write_all(b"\ntwo\nthr").unwrap();
+        out.write_all(b"ee").unwrap();
+        drop(out);
+
+        let sent: Vec<_> = receiver
+            .iter()
+            .map(|output| match output {
+                Output::Lines(lines) => String::from_utf8(lines).unwrap(),
+                Output::Done(_) => unreachable!(),
+            })
+            .collect();
+        assert_eq!(vec!["one\ntwo\n"], sent);
+    }
+}
@@ -0,0 +1,673 @@
+//! A trigram index of a directory, built by `minigrep index build DIR` and
+//! used by `--index`.
+//!
+//! For every three bytes that occur in the case-folded text of some file,
+//! the index lists the files they occur in. A search with `--index` works
+//! out which trigrams a line must contain to match, and only opens the
+//! files that have them. Folding case the way `-i` does lets one index
+//! serve searches with and without it.
+//!
+//! The index only ever rules files out: files that changed since it was
+//! built, going by their size and modification time, and files it does not
+//! know about are searched as usual. So are files modified within `RACY` of
+//! the build, since they may have changed again after being read without
+//! their modification time showing it. A change that keeps both the size
+//! and the modification time, such as one made by a tool that restores the
+//! time afterwards, goes unnoticed until the index is built again.
+
+use crate::walk::Walker;
+use crate::Config;
+use regex_syntax::hir::literal::Extractor;
+use searcher::Mode;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, Metadata};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tempfile::NamedTempFile;
+
+/// The file the index of a directory is kept in, inside that directory.
+pub const INDEX_FILE: &str = ".minigrep-index";
+
+/// The start of every index file; the number is the format version.
+const MAGIC: &[u8] = b"minigrep index 2\n\0";
+
+/// How close to the build a file's modification time must be for the index
+/// not to trust it: longer than the coarsest timestamps of common file
+/// systems, which is two seconds on FAT.
+const RACY: Duration = Duration::from_secs(2);
+
+/// Three bytes of case-folded text, big-endian in the low 24 bits.
+type Trigram = u32;
+
+/// Every distinct trigram of `text` once its case is folded, in order.
+fn trigrams(text: &str) -> Vec<Trigram> {
+    let folded = caseless::default_case_fold_str(text);
+    let mut trigrams: Vec<Trigram> = folded
+        .as_bytes()
+        .windows(3)
+        .map(|w| u32::from_be_bytes([0, w[0], w[1], w[2]]))
+        .collect();
+
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
+
+/// What a file looked like when it was indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Stamp {
+    modified: Duration,
+    len: u64,
+}
+
+impl Stamp {
+    fn now() -> Duration {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+
+    fn of(metadata: &Metadata) -> io::Result<Stamp> {
+        Ok(Stamp {
+            modified: metadata
+                .modified()?
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default(),
+            len: metadata.len(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    /// Relative to the indexed directory.
+    path: String,
+    stamp: Stamp,
+}
+
+/// The trigram index of one directory.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Index {
+    root: PathBuf,
+    /// When the build started, since the Unix epoch.
+    built: Duration,
+    files: Vec<Entry>,
+    /// For each trigram, the positions in `files` of the files it occurs
+    /// in, in ascending order.
+    postings: BTreeMap<Trigram, Vec<u32>>,
+    /// Positions in `files` by path.
+    ids: HashMap<String, u32>,
+}
+
+impl Index {
+    fn new(root: &Path, built: Duration, files: Vec<(Entry, Vec<Trigram>)>) -> Index {
+        let mut postings = BTreeMap::<Trigram, Vec<u32>>::new();
+        let mut entries = Vec::with_capacity(files.len());
+
+        for (id, (entry, trigrams)) in files.into_iter().enumerate() {
+            for trigram in trigrams {
+                postings.entry(trigram).or_default().push(id as u32);
+            }
+            entries.push(entry);
+        }
+
+        Index::from_parts(root, built, entries, postings)
+    }
+
+    fn from_parts(
+        root: &Path,
+        built: Duration,
+        files: Vec<Entry>,
+        postings: BTreeMap<Trigram, Vec<u32>>,
+    ) -> Index {
+        let ids = files
+            .iter()
+            .enumerate()
+            .map(|(id, entry)| (entry.path.clone(), id as u32))
+            .collect();
+
+        Index {
+            root: root.to_path_buf(),
+            built,
+            files,
+            postings,
+            ids,
+        }
+    }
+
+    /// Read the index of the directory `root`.
+    pub fn open(root: &Path) -> io::Result<Index> {
+        let bytes = fs::read(root.join(INDEX_FILE))?;
+        Index::decode(root, &bytes)
+    }
+
+    fn save(&self) -> io::Result<()> {
+        // Searches running meanwhile see either the old index or the new
+        // one, never half of one.
+        let mut file = NamedTempFile::new_in(&self.root)?;
+        file.write_all(&self.encode())?;
+        file.persist(self.root.join(INDEX_FILE))
+            .map_err(|e| e.error)?;
+        Ok(())
+    }
+
+    /// Whether the file with position `id` still looks like `stamp`, as it
+    /// did when it was indexed, and was not modified so close to the build
+    /// that it may have changed since without that showing.
+    fn unchanged(&self, id: usize, stamp: Stamp) -> bool {
+        let entry = &self.files[id];
+        entry.stamp == stamp && entry.stamp.modified + RACY < self.built
+    }
+
+    /// The trigrams of each file, undoing `new`.
+    fn trigrams_by_file(&self) -> Vec<Vec<Trigram>> {
+        let mut by_file = vec![Vec::new(); self.files.len()];
+
+        for (&trigram, ids) in &self.postings {
+            for &id in ids {
+                by_file[id as usize].push(trigram);
+            }
+        }
+
+        by_file
+    }
+
+    /// Whether each file has every trigram of at least one alternative of
+    /// `query`.
+    fn candidates(&self, query: &Query) -> Vec<bool> {
+        let mut candidates = vec![false; self.files.len()];
+
+        for trigrams in &query.0 {
+            let mut ids: Option<Vec<u32>> = None;
+            for trigram in trigrams {
+                let posting = self.postings.get(trigram).map_or(&[][..], Vec::as_slice);
+                let ids = ids.get_or_insert_with(|| posting.to_vec());
+                ids.retain(|id| posting.binary_search(id).is_ok());
+                if ids.is_empty() {
+                    break;
+                }
+            }
+
+            for id in ids.into_iter().flatten() {
+                candidates[id as usize] = true;
+            }
+        }
+
+        candidates
+    }
+
+    /// The on-disk format: `MAGIC`, then the build time, the files and then
+    /// the postings.
+    /// Numbers are LEB128 varints, and trigrams and the ids in each posting
+    /// are stored as the gap from the one before, which keeps them small.
+    fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+
+        put(&mut out, self.built.as_secs());
+        put(&mut out, self.built.subsec_nanos().into());
+        put(&mut out, self.files.len() as u64);
+        for entry in &self.files {
+            put(&mut out, entry.path.len() as u64);
+            out.extend_from_slice(entry.path.as_bytes());
+            put(&mut out, entry.stamp.modified.as_secs());
+            put(&mut out, entry.stamp.modified.subsec_nanos().into());
+            put(&mut out, entry.stamp.len);
+        }
+
+        put(&mut out, self.postings.len() as u64);
+        let mut previous = 0;
+        for (&trigram, ids) in &self.postings {
+            put(&mut out, (trigram - previous).into());
+            previous = trigram;
+
+            put(&mut out, ids.len() as u64);
+            let mut previous = 0;
+            for &id in ids {
+                put(&mut out, (id - previous).into());
+                previous = id;
+            }
+        }
+
+        out
+    }
+
+    fn decode(root: &Path, bytes: &[u8]) -> io::Result<Index> {
+        let mut input = Decoder(
+            bytes
+                .strip_prefix(MAGIC)
+                .ok_or_else(|| corrupt("not a minigrep index"))?,
+        );
+
+        let built = input.time()?;
+        let mut files = Vec::new();
+        for _ in 0..input.varint()? {
+            let len = input.number()?;
+            let path = str::from_utf8(input.take(len)?).map_err(|_| corrupt("bad path"))?;
+            files.push(Entry {
+                path: path.to_string(),
+                stamp: Stamp {
+                    modified: input.time()?,
+                    len: input.varint()?,
+                },
+            });
+        }
+
+        let mut postings = BTreeMap::new();
+        let mut trigram: Trigram = 0;
+        for i in 0..input.varint()? {
+            let gap: Trigram = input.number()?;
+            trigram = trigram
+                .checked_add(gap)
+                .filter(|&t| (i == 0 || gap > 0) && t < 1 << 24)
+                .ok_or_else(|| corrupt("bad trigram"))?;
+
+            let mut ids = Vec::new();
+            let mut id: u32 = 0;
+            for j in 0..input.varint()? {
+                let gap: u32 = input.number()?;
+                id = id
+                    .checked_add(gap)
+                    .filter(|&id| (j == 0 || gap > 0) && (id as usize) < files.len())
+                    .ok_or_else(|| corrupt("bad file id"))?;
+                ids.push(id);
+            }
+            postings.insert(trigram, ids);
+        }
+
+        if !input.0.is_empty() {
+            return Err(corrupt("trailing data"));
+        }
+
+        Ok(Index::from_parts(root, built, files, postings))
+    }
+}
+
+fn put(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+struct Decoder<'a>(&'a [u8]);
+
+impl<'a> Decoder<'a> {
+    fn varint(&mut self) -> io::Result<u64> {
+        let mut n = 0;
+
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = self.0.split_first().ok_or_else(|| corrupt("truncated"))?;
+            self.0 = rest;
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte < 0x80 {
+                return Ok(n);
+            }
+        }
+
+        Err(corrupt("varint too long"))
+    }
+
+    /// A varint that must fit in `T`.
+    fn number<T: TryFrom<u64>>(&mut self) -> io::Result<T> {
+        T::try_from(self.varint()?).map_err(|_| corrupt("number out of range"))
+    }
+
+    /// Seconds and then nanoseconds.
+    fn time(&mut self) -> io::Result<Duration> {
+        let secs = self.varint()?;
+        let nanos = self.number()?;
+        if nanos >= 1_000_000_000 {
+            return Err(corrupt("bad time"));
+        }
+        Ok(Duration::new(secs, nanos))
+    }
+
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if len > self.0.len() {
+            return Err(corrupt("truncated"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+}
+
+fn corrupt(why: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("corrupt index: {why}"))
+}
+
+/// What `build` did.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct BuildStats {
+    /// Files in the index.
+    pub files: usize,
+    /// Files that were new or had changed, and so were read.
+    pub read: usize,
+    /// Files that were in the old index but are gone.
+    pub removed: usize,
+}
+
+/// Build the index of `root`, or bring it up to date. Only files that are
+/// new or changed are read; the rest keep their trigrams from the old
+/// index. Files are chosen as a search of `root` with no options would
+/// choose them, skipping hidden and ignored files.
+///
+/// Files that cannot be read are reported on stderr and left out, which
+/// means searches always open them.
+pub fn build(root: &Path) -> io::Result<BuildStats> {
+    // An index that is missing, or that we cannot make sense of, is simply
+    // built again from scratch.
+    let old = Index::open(root).ok();
+    let mut old_trigrams = old.as_ref().map_or_else(Vec::new, Index::trigrams_by_file);
+
+    let built = Stamp::now();
+    let walker = Walker::new(&[], &[], false, false).map_err(io::Error::other)?;
+    let mut files = Vec::new();
+    let mut stats = BuildStats::default();
+
+    for path in walker.files(&[root.to_path_buf()]) {
+        let indexed = path.map_err(io::Error::other).and_then(|path| {
+            // Files with names that are not UTF-8 are left out.
+            let Some(relative) = path.strip_prefix(root).ok().and_then(Path::to_str) else {
+                return Ok(None);
+            };
+
+            // Stamp the file before reading it, so that if it changes
+            // meanwhile the index looks stale rather than up to date.
+            let stamp = Stamp::of(&fs::metadata(&path)?)?;
+            let unchanged = old
+                .as_ref()
+                .and_then(|old| old.ids.get(relative).map(|&id| (old, id as usize)))
+                .filter(|&(old, id)| old.unchanged(id, stamp));
+
+            let trigrams = match unchanged {
+                Some((_, id)) => std::mem::take(&mut old_trigrams[id]),
+                None => {
+                    stats.read += 1;
+                    trigrams(&String::from_utf8_lossy(&fs::read(&path)?))
+                }
+            };
+
+            let path = relative.to_string();
+            Ok(Some((Entry { path, stamp }, trigrams)))
+        });
+
+        match indexed {
+            Ok(Some(file)) => files.push(file),
+            Ok(None) => {}
+            Err(e) => eprintln!("minigrep: {e}"),
+        }
+    }
+
+    stats.files = files.len();
+    let index = Index::new(root, built, files);
+    stats.removed = old.map_or(0, |old| {
+        let gone = old
+            .files
+            .iter()
+            .filter(|entry| !index.ids.contains_key(&entry.path));
+        gone.count()
+    });
+    index.save()?;
+
+    Ok(stats)
+}
+
+/// What a file must contain for a search to find something in it: every
+/// trigram of at least one of the alternatives.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Query(Vec<Vec<Trigram>>);
+
+impl Query {
+    /// The query for a search as `config` asks, or `None` if any file
+    /// could produce output.
+    pub fn new(config: &Config) -> Option<Query> {
+        // Files without a match still have lines to select, counts to
+        // print or names to list.
+        if config.searcher.invert_match() || config.count || config.files_without_match {
+            return None;
+        }
+
+        let mut alternatives = Vec::new();
+        for pattern in &config.patterns {
+            let literals = match config.mode {
+                Mode::Literal => vec![pattern.clone()],
+                Mode::Regex => prefixes(pattern)?,
+            };
+
+            for literal in literals {
+                let trigrams = trigrams(&literal);
+                // Too short to rule anything out.
+                if trigrams.is_empty() {
+                    return None;
+                }
+                alternatives.push(trigrams);
+            }
+        }
+
+        Some(Query(alternatives))
+    }
+}
+
+/// Strings one of which every match of the regular expression `pattern`
+/// starts with, or `None` if there are too many to list.
+///
+/// The pattern is parsed as if case mattered even when it does not: the
+/// literals are folded later, and text that the regex matches ignoring
+/// case folds to the same thing as the literal does.
+fn prefixes(pattern: &str) -> Option<Vec<String>> {
+    let hir = regex_syntax::Parser::new().parse(pattern).ok()?;
+    let prefixes = Extractor::new().extract(&hir);
+
+    let literals = prefixes.literals()?.iter().map(|literal| {
+        // A long literal can be cut short in the middle of a character;
+        // the characters before that are still a prefix of every match.
+        let bytes = literal.as_bytes();
+        let valid = match str::from_utf8(bytes) {
+            Ok(text) => text,
+            Err(e) => str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+        };
+        valid.to_string()
+    });
+
+    Some(literals.collect())
+}
+
+/// Decides which of the files found under the directories being searched
+/// need to be opened, using the index of each directory or of the nearest
+/// directory above it that has one.
+pub struct Filter {
+    searches: Vec<Search>,
+}
+
+/// One directory being searched and the index that covers it.
+struct Search {
+    /// The directory, as it was given.
+    dir: PathBuf,
+    /// Where `dir` is inside the indexed directory.
+    within: PathBuf,
+    index: Index,
+    /// Whether each file of the index may match.
+    candidates: Vec<bool>,
+}
+
+impl Filter {
+    /// Find the index covering each directory in `paths`, which must have
+    /// one.
+    pub fn new(paths: &[PathBuf], query: Option<&Query>) -> io::Result<Filter> {
+        let mut searches = Vec::new();
+
+        for dir in paths.iter().filter(|path| path.is_dir()) {
+            let (index, within) = Filter::nearest(dir)
+                .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", dir.display())))?;
+
+            let candidates = match query {
+                Some(query) => index.candidates(query),
+                None => vec![true; index.files.len()],
+            };
+            searches.push(Search {
+                dir: dir.clone(),
+                within,
+                index,
+                candidates,
+            });
+        }
+
+        Ok(Filter { searches })
+    }
+
+    /// The index of `dir` or of the nearest directory above it, and where
+    /// `dir` is inside the indexed directory.
+    fn nearest(dir: &Path) -> io::Result<(Index, PathBuf)> {
+        let dir = fs::canonicalize(dir)?;
+
+        for root in dir.ancestors() {
+            match Index::open(root) {
+                Ok(index) => {
+                    let within = dir.strip_prefix(root).unwrap_or(&dir);
+                    return Ok((index, within.to_path_buf()));
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            "no index here or in any directory above; build one with `minigrep index build DIR`",
+        ))
+    }
+
+    /// Whether `path` needs searching: it is not in an index, it changed
+    /// since it was indexed, or its index says it may match.
+    pub fn may_match(&self, path: &Path) -> bool {
+        let found = self.searches.iter().find_map(|search| {
+            let relative = search.within.join(path.strip_prefix(&search.dir).ok()?);
+            let &id = search.index.ids.get(relative.to_str()?)?;
+            Some((search, id as usize))
+        });
+        let Some((search, id)) = found else {
+            return true;
+        };
+
+        let unchanged = fs::metadata(path)
+            .and_then(|metadata| Stamp::of(&metadata))
+            .is_ok_and(|stamp| search.index.unchanged(id, stamp));
+        search.candidates[id] || !unchanged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{args, tree};
+
+    fn query(list: &[&str]) -> Option<Query> {
+        Query::new(&Config::build(args(list)).unwrap())
+    }
+
+    const FILES: &[(&str, &str)] = &[
+        ("a.txt", "hello world\n"),
+        ("b.txt", "nothing here\n"),
+        ("sub/c.txt", "HELLO\n"),
+    ];
+
+    fn may_match(dir: &Path, list: &[&str], file: &Path) -> bool {
+        let filter = Filter::new(&[dir.to_path_buf()], query(list).as_ref()).unwrap();
+        filter.may_match(file)
+    }
+
+    #[test]
+    fn trigrams_are_case_folded() {
+        assert_eq!(trigrams("abcss"), trigrams("ABcß"));
+        assert_eq!(3, trigrams("ABcß").len());
+        assert!(trigrams("ab").is_empty());
+    }
+
+    #[test]
+    fn queries_need_the_trigrams_of_some_literal() {
+        assert_eq!(
+            Some(Query(vec![trigrams("hello"), trigrams("world")])),
+            query(&["minigrep", "-e", "hello", "-e", "World"])
+        );
+        assert_eq!(
+            Some(Query(vec![trigrams("foobar"), trigrams("foobaz")])),
+            query(&["minigrep", "-E", "foo(bar|baz)+"])
+        );
+        assert_eq!(None, query(&["minigrep", "-E", r"\w+foo"]));
+        assert_eq!(None, query(&["minigrep", "ab"]));
+        assert_eq!(None, query(&["minigrep", "-v", "hello"]));
+        assert_eq!(None, query(&["minigrep", "-L", "hello"]));
+    }
+
+    #[test]
+    fn encodes_and_decodes() {
+        let dir = tree(FILES);
+        let root = dir.path();
+        build(root).unwrap();
+        let index = Index::open(root).unwrap();
+        assert_eq!(3, index.files.len());
+
+        let bytes = index.encode();
+        assert_eq!(index, Index::decode(root, &bytes).unwrap());
+        assert!(Index::decode(root, &bytes[..bytes.len() - 1]).is_err());
+        assert!(Index::decode(root, b"hello").is_err());
+    }
+
+    #[test]
+    fn builds_incrementally_and_narrows_searches() {
+        let dir = tree(FILES);
+        let root = dir.path();
+        let stats = |files, read, removed| BuildStats {
+            files,
+            read,
+            removed,
+        };
+        let may_match = |list: &[&str], file: &str| may_match(root, list, &root.join(file));
+
+        assert_eq!(stats(3, 3, 0), build(root).unwrap());
+        assert_eq!(stats(3, 0, 0), build(root).unwrap());
+
+        assert!(may_match(&["minigrep", "hello"], "a.txt"));
+        assert!(!may_match(&["minigrep", "hello"], "b.txt"));
+        // The index folds case, so it cannot rule out sub/c.txt.
+        assert!(may_match(&["minigrep", "hello"], "sub/c.txt"));
+        assert!(may_match(&["minigrep", "-v", "hello"], "b.txt"));
+
+        // Changed files are searched before the index catches up.
+        fs::write(root.join("b.txt"), "hello again\n").unwrap();
+        assert!(may_match(&["minigrep", "hello"], "b.txt"));
+        fs::remove_file(root.join("sub/c.txt")).unwrap();
+        fs::write(root.join("d.txt"), "new\n").unwrap();
+        assert_eq!(stats(3, 2, 1), build(root).unwrap());
+    }
+
+    #[test]
+    fn searches_files_modified_as_the_index_was_built() {
+        let dir = tree(FILES);
+        let root = dir.path();
+        fs::write(root.join("b.txt"), "nothing new\n").unwrap();
+        build(root).unwrap();
+
+        // b.txt may have changed again since it was read.
+        assert!(may_match(root, &["minigrep", "hello"], &root.join("b.txt")));
+        assert_eq!(1, build(root).unwrap().read);
+    }
+
+    #[test]
+    fn uses_the_nearest_index_above() {
+        let dir = tree(FILES);
+        let root = dir.path();
+        build(root).unwrap();
+
+        let sub = root.join("sub");
+        assert!(!may_match(&sub, &["minigrep", "world"], &sub.join("c.txt")));
+        assert!(may_match(&sub, &["minigrep", "hello"], &sub.join("c.txt")));
+
+        let unindexed = tempfile::tempdir().unwrap();
+        assert!(Filter::new(&[unindexed.path().to_path_buf()], None).is_err());
+    }
+}
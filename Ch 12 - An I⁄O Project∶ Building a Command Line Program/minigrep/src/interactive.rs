@@ -0,0 +1,533 @@
+//! `--interactive`: a terminal UI that searches as the query is typed.
+
+use crate::{decompress, Config, STDIN_PATH};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use searcher::{Match, Sink};
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+/// Stop collecting results after this many rows, so that a query that
+/// matches nearly everything stays responsive.
+const MAX_ROWS: usize = 10_000;
+
+/// How long typing has to pause before the search is run again.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// One row of the results list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Row {
+    /// A matching line, or a line of context around one.
+    Line {
+        path: PathBuf,
+        line_number: u64,
+        text: String,
+        /// Where the query matched; empty for context lines.
+        spans: Vec<Range<usize>>,
+        is_match: bool,
+    },
+    /// Lines before and after this do not follow on from each other.
+    Break,
+}
+
+/// The results of searching for one query.
+#[derive(Debug, Default)]
+pub struct Results {
+    pub rows: Vec<Row>,
+    pub matches: u64,
+    pub files_matched: u64,
+    /// Whether rows were left out after `MAX_ROWS`.
+    pub truncated: bool,
+    /// Why each path that could not be searched could not be.
+    pub errors: Vec<String>,
+}
+
+/// Collects the rows for one file.
+struct Collect<'a> {
+    path: &'a Path,
+    rows: &'a mut Vec<Row>,
+    /// Whether to mark where one file's rows end and the next begin.
+    breaks: bool,
+    started: bool,
+    cancel: &'a AtomicBool,
+}
+
+impl Collect<'_> {
+    fn push(&mut self, line_number: u64, text: &str, spans: &[Range<usize>], is_match: bool) {
+        if !self.started && self.breaks && !self.rows.is_empty() {
+            self.rows.push(Row::Break);
+        }
+        self.started = true;
+
+        self.rows.push(Row::Line {
+            path: self.path.to_path_buf(),
+            line_number,
+            text: text.to_string(),
+            spans: spans.to_vec(),
+            is_match,
+        });
+    }
+
+    /// Whether to keep searching.
+    fn has_room(&self) -> bool {
+        self.rows.len() < MAX_ROWS && !self.cancel.load(Ordering::Relaxed)
+    }
+}
+
+impl Sink for Collect<'_> {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        self.push(m.line_number, m.line, &m.spans, true);
+        Ok(self.has_room())
+    }
+
+    fn context(&mut self, line_number: u64, _: u64, line: &str) -> io::Result<bool> {
+        self.push(line_number, line, &[], false);
+        Ok(self.has_room())
+    }
+
+    fn context_break(&mut self) -> io::Result<bool> {
+        self.rows.push(Row::Break);
+        Ok(true)
+    }
+}
+
+/// Search the paths in `config` for `query`, with the rest of the options
+/// `config` was built with. Setting `cancel` stops the search early, with
+/// whatever it has found so far.
+pub fn search(config: &Config, query: &str, cancel: &AtomicBool) -> Result<Results, String> {
+    let mut results = Results::default();
+    if query.is_empty() {
+        return Ok(results);
+    }
+
+    let searcher = config.builder.clone().pattern(query).build()?;
+    let breaks = searcher.before_context() > 0 || searcher.after_context() > 0;
+
+    for file in config.walker.files(&config.paths) {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+        let path = match file {
+            Ok(path) => path,
+            Err(e) => {
+                results.errors.push(e);
+                continue;
+            }
+        };
+
+        let mut sink = Collect {
+            path: &path,
+            rows: &mut results.rows,
+            breaks,
+            started: false,
+            cancel,
+        };
+        let searched = File::open(&path).and_then(|file| {
+            let reader: Box<dyn BufRead> = if config.search_zip {
+                decompress::reader(&path, BufReader::new(file))?
+            } else {
+                Box::new(BufReader::new(file))
+            };
+            searcher.search_reader(reader, &mut sink)
+        });
+
+        match searched {
+            Ok(0) => {}
+            Ok(count) => {
+                results.matches += count;
+                results.files_matched += 1;
+            }
+            Err(e) => results.errors.push(format!("{}: {e}", path.display())),
+        }
+
+        if results.rows.len() >= MAX_ROWS {
+            results.truncated = true;
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+/// The command that opens `path` at `line_number` in `editor`, which may
+/// include flags, such as `code -w`. Nearly every terminal editor accepts
+/// `+LINE` before the file name.
+pub fn editor_command(editor: &str, path: &Path, line_number: u64) -> Command {
+    let mut words = editor.split_whitespace();
+    let mut command = Command::new(words.next().unwrap_or("vi"));
+    command.args(words).arg(format!("+{line_number}")).arg(path);
+    command
+}
+
+struct App {
+    config: Arc<Config>,
+    query: String,
+    results: Results,
+    /// Why the last query could not be searched, such as a bad regex.
+    error: Option<String>,
+    list: ListState,
+    /// Whether the query has changed since the last search.
+    stale: bool,
+    /// The search running in the background, if any.
+    running: Option<Running>,
+    /// Rows of results that fit on screen, for Page Up and Page Down.
+    page: usize,
+}
+
+/// A search on a thread of its own, so that typing never waits for one.
+struct Running {
+    cancel: Arc<AtomicBool>,
+    results: mpsc::Receiver<Result<Results, String>>,
+}
+
+impl Drop for Running {
+    /// Nobody wants the results of a search that has been replaced.
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Open the terminal UI and search the paths in `config` as the user types.
+pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    if config
+        .paths
+        .iter()
+        .any(|path| path == Path::new(STDIN_PATH))
+    {
+        return Err("--interactive needs files to search, not standard input".into());
+    }
+    if !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        return Err("--interactive needs a terminal".into());
+    }
+
+    let mut app = App {
+        config: Arc::new(config),
+        query: String::new(),
+        results: Results::default(),
+        error: None,
+        list: ListState::default(),
+        stale: false,
+        running: None,
+        page: 1,
+    };
+
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<(), Box<dyn Error>> {
+        loop {
+            self.receive();
+            terminal.draw(|frame| self.draw(frame))?;
+
+            // Only search again once typing pauses, so that fast typing
+            // does not start searches for queries nobody wants. While a
+            // search runs, wake up now and then to show its results.
+            if (self.stale || self.running.is_some()) && !event::poll(DEBOUNCE)? {
+                if self.stale {
+                    self.search();
+                }
+                continue;
+            }
+
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            if !self.handle_key(key, terminal)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Handle one key press, returning false to quit.
+    fn handle_key(&mut self, key: KeyEvent, terminal: &mut DefaultTerminal) -> io::Result<bool> {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+
+        match key.code {
+            KeyCode::Esc => return Ok(false),
+            KeyCode::Char('c') if ctrl => return Ok(false),
+            KeyCode::Char('u') if ctrl => self.edit(String::clear),
+            KeyCode::Char(c) if !ctrl => self.edit(|query| query.push(c)),
+            KeyCode::Backspace => self.edit(|query| {
+                query.pop();
+            }),
+            KeyCode::Up => self.list.select_previous(),
+            KeyCode::Down => self.list.select_next(),
+            KeyCode::PageUp => self.list.scroll_up_by(self.page as u16),
+            KeyCode::PageDown => self.list.scroll_down_by(self.page as u16),
+            KeyCode::Home => self.list.select_first(),
+            KeyCode::End => self.list.select_last(),
+            KeyCode::Enter => self.open_selected(terminal)?,
+            _ => {}
+        }
+
+        Ok(true)
+    }
+
+    fn edit(&mut self, change: impl FnOnce(&mut String)) {
+        change(&mut self.query);
+        self.stale = true;
+    }
+
+    /// Start searching for the query in the background, cancelling the
+    /// search for the last one.
+    fn search(&mut self) {
+        self.stale = false;
+
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let (config, query, flag) = (
+            Arc::clone(&self.config),
+            self.query.clone(),
+            Arc::clone(&cancel),
+        );
+        thread::spawn(move || {
+            let _ = sender.send(search(&config, &query, &flag));
+        });
+
+        self.running = Some(Running {
+            cancel,
+            results: receiver,
+        });
+    }
+
+    /// Show the results of the running search, if it has finished.
+    fn receive(&mut self) {
+        let Some(running) = &self.running else {
+            return;
+        };
+        let result = match running.results.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => Err("search failed".to_string()),
+        };
+        self.running = None;
+
+        match result {
+            Ok(results) => {
+                self.list.select((!results.rows.is_empty()).then_some(0));
+                self.results = results;
+                self.error = None;
+            }
+            // Keep showing the last results while a regex is half typed.
+            Err(e) => self.error = Some(e),
+        }
+    }
+
+    /// Open the selected line in the user's editor, handing the terminal
+    /// over to it until it exits.
+    fn open_selected(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let selected = self.list.selected().and_then(|i| self.results.rows.get(i));
+        let Some(Row::Line {
+            path, line_number, ..
+        }) = selected
+        else {
+            return Ok(());
+        };
+
+        let editor = env::var("VISUAL")
+            .or_else(|_| env::var("EDITOR"))
+            .unwrap_or_else(|_| "vi".to_string());
+
+        ratatui::restore();
+        let status = editor_command(&editor, path, *line_number).status();
+        *terminal = ratatui::try_init()?;
+        terminal.clear()?;
+
+        match status {
+            Ok(status) if !status.success() => {
+                self.error = Some(format!("{editor} exited with {status}"));
+            }
+            Err(e) => self.error = Some(format!("could not run {editor}: {e}")),
+            Ok(_) => {}
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [input, results, status] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(1),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let query = Line::from(self.query.as_str());
+        let cursor_x = input.x + 1 + query.width() as u16;
+        frame.render_widget(
+            Paragraph::new(query).block(Block::bordered().title(" Query ")),
+            input,
+        );
+        frame.set_cursor_position((cursor_x.min(input.right().saturating_sub(2)), input.y + 1));
+
+        self.page = usize::from(results.height.saturating_sub(2)).max(1);
+        let items: Vec<ListItem> = self.results.rows.iter().map(row_line).collect();
+        let list = List::new(items)
+            .block(Block::bordered().title(" Results "))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, results, &mut self.list);
+
+        frame.render_widget(Paragraph::new(self.status()), status);
+    }
+
+    fn status(&self) -> Line<'_> {
+        if let Some(error) = &self.error {
+            return Line::from(error.as_str()).red();
+        }
+
+        let results = &self.results;
+        let mut status = format!(
+            "{} matching lines in {} files",
+            results.matches, results.files_matched
+        );
+        if results.truncated {
+            status += &format!(", first {MAX_ROWS} rows shown");
+        }
+        match results.errors.as_slice() {
+            [] => {}
+            [error] => status += &format!(", {error}"),
+            [error, rest @ ..] => {
+                status += &format!(", {error} and {} more errors", rest.len());
+            }
+        }
+        if self.running.is_some() {
+            status += " · searching…";
+        }
+        status += " · Enter: open in editor · Esc: quit";
+        Line::from(status).dim()
+    }
+}
+
+/// How a row looks in the list: `path:line:text` for matches and
+/// `path-line-text` for context, coloured like minigrep's usual output.
+fn row_line(row: &Row) -> ListItem<'_> {
+    let Row::Line {
+        path,
+        line_number,
+        text,
+        spans,
+        is_match,
+    } = row
+    else {
+        return ListItem::new(Line::from("--").cyan());
+    };
+
+    let separator = if *is_match { ":" } else { "-" };
+    let mut parts = vec![
+        Span::raw(path.display().to_string()).magenta(),
+        Span::raw(separator).cyan(),
+        Span::raw(line_number.to_string()).green(),
+        Span::raw(separator).cyan(),
+    ];
+
+    let highlight = Style::new().fg(Color::Red).add_modifier(Modifier::BOLD);
+    let mut last = 0;
+    for span in spans {
+        parts.push(Span::raw(&text[last..span.start]));
+        parts.push(Span::styled(&text[span.clone()], highlight));
+        last = span.end;
+    }
+    parts.push(Span::raw(&text[last..]));
+
+    let line = Line::from(parts);
+    ListItem::new(if *is_match { line } else { line.dim() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::args;
+    use std::fs;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn searches_with_the_options_given_on_the_command_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input");
+        fs::write(&path, "one\ntwo\nthree\nfour\nfive\nsix\n").unwrap();
+        let name = path.to_str().unwrap();
+        let config =
+            Config::build(args(&["minigrep", "--interactive", "-i", "-A1", name])).unwrap();
+        assert_eq!(vec![path.clone()], config.paths);
+
+        let go = AtomicBool::new(false);
+        let results = search(&config, "O", &go).unwrap();
+        let line = |line_number: u64, text: &str, spans: Vec<Range<usize>>| Row::Line {
+            path: path.clone(),
+            line_number,
+            text: text.to_string(),
+            is_match: !spans.is_empty(),
+            spans,
+        };
+
+        assert_eq!(
+            vec![
+                line(1, "one", vec![0..1]),
+                line(2, "two", vec![2..3]),
+                line(3, "three", vec![]),
+                line(4, "four", vec![1..2]),
+                line(5, "five", vec![]),
+            ],
+            results.rows
+        );
+        assert_eq!((3, 1), (results.matches, results.files_matched));
+
+        assert!(search(&config, "", &go).unwrap().rows.is_empty());
+
+        let cancelled = AtomicBool::new(true);
+        assert!(search(&config, "O", &cancelled).unwrap().rows.is_empty());
+    }
+
+    #[test]
+    fn keeps_the_reason_a_path_could_not_be_searched() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("missing");
+        let config = Config::build(args(&[
+            "minigrep",
+            "--interactive",
+            missing.to_str().unwrap(),
+        ]))
+        .unwrap();
+
+        let results = search(&config, "x", &AtomicBool::new(false)).unwrap();
+        assert_eq!(1, results.errors.len());
+        assert!(results.errors[0].starts_with(missing.to_str().unwrap()));
+    }
+
+    #[test]
+    fn reports_queries_that_do_not_compile() {
+        let config = Config::build(args(&["minigrep", "--interactive", "-E", "."])).unwrap();
+
+        assert!(search(&config, "(unclosed", &AtomicBool::new(false)).is_err());
+    }
+
+    #[test]
+    fn opens_the_editor_at_the_line() {
+        let command = editor_command("code -w", Path::new("src/lib.rs"), 42);
+
+        assert_eq!("code", command.get_program());
+        assert_eq!(
+            vec!["-w", "+42", "src/lib.rs"],
+            command.get_args().collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! The `--json` output format: JSON Lines, one event object per line.
+//!
+//! Every event has a `type`. For each file searched, minigrep writes a
+//! `begin` event, then a `match` or `context` event per line printed, then
+//! an `end` event; after every file has been searched it writes one
+//! `summary` event. Files searched in parallel never interleave their
+//! events. Fields may be added to events in later versions, but existing
+//! fields will keep their names and meanings.
+//!
+//! ```text
+//! {"type":"begin","path":"poem.txt"}
+//! {"type":"context","path":"poem.txt","line_number":1,"byte_offset":0,"line":"I'm nobody! Who are you?"}
+//! {"type":"match","path":"poem.txt","line_number":2,"byte_offset":25,"line":"Are you nobody, too?","submatches":[{"text":"to","start":16,"end":18}]}
+//! {"type":"end","path":"poem.txt","matches":1,"binary":false}
+//! {"type":"summary","files_searched":1,"files_matched":1,"matches":1,"errors":0}
+//! ```
+//!
+//! - `path` is the file as given or found, or `(standard input)`.
+//! - `line_number` is 1-based; `byte_offset` is where the line starts in
+//!   the file. `line` has no line terminator, and invalid UTF-8 in it is
+//!   replaced with U+FFFD.
+//! - `submatches` lists each match within `line`; `start` and `end` are
+//!   byte offsets into `line`. It is empty for `--invert-match`.
+//! - `matches` counts the lines selected. For a binary file, `binary` is
+//!   true and the search stops at the first match, without a `match` event.
+//! - `errors` counts the paths that could not be searched; each is also
+//!   reported on standard error.
+
+use searcher::Match;
+use serde::Serialize;
+use std::io::{self, Write};
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Event<'a> {
+    Begin {
+        path: &'a str,
+    },
+    Match {
+        path: &'a str,
+        line_number: u64,
+        byte_offset: u64,
+        line: &'a str,
+        submatches: Vec<Submatch<'a>>,
+    },
+    Context {
+        path: &'a str,
+        line_number: u64,
+        byte_offset: u64,
+        line: &'a str,
+    },
+    End {
+        path: &'a str,
+        matches: u64,
+        binary: bool,
+    },
+    Summary(&'a Summary),
+}
+
+#[derive(Serialize)]
+struct Submatch<'a> {
+    text: &'a str,
+    start: usize,
+    end: usize,
+}
+
+/// Totals for the whole run.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+pub struct Summary {
+    pub files_searched: u64,
+    pub files_matched: u64,
+    pub matches: u64,
+    pub errors: u64,
+}
+
+impl Summary {
+    /// Count a file that was searched and had `matches` lines selected.
+    pub fn add_file(&mut self, matches: u64) {
+        self.files_searched += 1;
+        self.matches += matches;
+        if matches > 0 {
+            self.files_matched += 1;
+        }
+    }
+}
+
+fn write(out: &mut impl Write, event: &Event) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, event)?;
+    writeln!(out)
+}
+
+pub fn begin(out: &mut impl Write, path: &str) -> io::Result<()> {
+    write(out, &Event::Begin { path })
+}
+
+pub fn matched(out: &mut impl Write, path: &str, m: &Match) -> io::Result<()> {
+    let submatches = m
+        .spans
+        .iter()
+        .map(|span| Submatch {
+            text: &m.line[span.clone()],
+            start: span.start,
+            end: span.end,
+        })
+        .collect();
+
+    write(
+        out,
+        &Event::Match {
+            path,
+            line_number: m.line_number,
+            byte_offset: m.byte_offset,
+            line: m.line,
+            submatches,
+        },
+    )
+}
+
+pub fn context(
+    out: &mut impl Write,
+    path: &str,
+    line_number: u64,
+    byte_offset: u64,
+    line: &str,
+) -> io::Result<()> {
+    write(
+        out,
+        &Event::Context {
+            path,
+            line_number,
+            byte_offset,
+            line,
+        },
+    )
+}
+
+pub fn end(out: &mut impl Write, path: &str, matches: u64, binary: bool) -> io::Result<()> {
+    write(
+        out,
+        &Event::End {
+            path,
+            matches,
+            binary,
+        },
+    )
+}
+
+pub fn summary(out: &mut impl Write, summary: &Summary) -> io::Result<()> {
+    write(out, &Event::Summary(summary))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn writes_one_object_per_line() {
+        let mut out = Vec::new();
+        let m = Match {
+            line_number: 2,
+            byte_offset: 25,
+            line: "Are you nobody, too?",
+            spans: vec![16..18],
+        };
+
+        begin(&mut out, "poem.txt").unwrap();
+        matched(&mut out, "poem.txt", &m).unwrap();
+        end(&mut out, "poem.txt", 1, false).unwrap();
+        summary(
+            &mut out,
+            &Summary {
+                files_searched: 1,
+                files_matched: 1,
+                matches: 1,
+                errors: 0,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            r#"{"type":"begin","path":"poem.txt"}
+{"type":"match","path":"poem.txt","line_number":2,"byte_offset":25,"line":"Are you nobody, too?","submatches":[{"text":"to","start":16,"end":18}]}
+{"type":"end","path":"poem.txt","matches":1,"binary":false}
+{"type":"summary","files_searched":1,"files_matched":1,"matches":1,"errors":0}
+"#,
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -1,76 +1,507 @@
+use clap::{error::ErrorKind, CommandFactory, Parser};
 use std::env;
 use std::error::Error;
-use std::fs;
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::thread;
+
+pub mod cli;
+pub mod decompress;
+pub mod follow;
+pub mod index;
+pub mod interactive;
+pub mod json;
+pub mod parallel;
+pub mod printer;
+pub mod replace;
+pub mod search;
+pub mod structured;
+pub mod walk;
+
+#[cfg(test)]
+mod test_support;
+
+use cli::{Args, ColorChoice, Command, IndexCommand, SortBy};
+use follow::{Follower, Output};
+use json::Summary;
+use memmap2::Mmap;
+use printer::{Colors, Printer};
+use search::{search_reader, search_slice};
+use searcher::{CaseMode, Mode, Searcher, SearcherBuilder};
+use structured::{Field, JsonPath};
+use walk::Walker;
+
+pub use searcher::{search, search_case_insensitive};
+
+/// The path that stands for standard input.
+const STDIN_PATH: &str = "-";
+
+/// Files smaller than this are read rather than memory-mapped, since for
+/// them setting up the map costs more than copying.
+const MMAP_MIN_LEN: u64 = 64 * 1024;
+
+/// How much of a file is read at once. The searcher looks for a required
+/// literal a block at a time, so bigger blocks mean fewer, longer runs of
+/// the fast substring search.
+const READ_BUFFER_LEN: usize = 256 * 1024;
 
 pub struct Config {
-    pub query: String,
-    pub file_path: String,
+    /// Lines matching any of these are selected.
+    pub patterns: Vec<String>,
+    pub paths: Vec<PathBuf>,
     pub ignore_case: bool,
+    pub mode: Mode,
+    /// Finds matching lines and their context.
+    pub searcher: Searcher,
+    /// How `searcher` was set up, minus the patterns, so that interactive
+    /// mode can build a new one for each query typed.
+    pub builder: SearcherBuilder,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub count: bool,
+    pub files_with_matches: bool,
+    pub files_without_match: bool,
+    /// Print nothing and stop at the first match.
+    pub quiet: bool,
+    pub color: ColorChoice,
+    /// What to colour each part of the output with, from MINIGREP_COLORS.
+    pub colors: Colors,
+    /// Whether to prefix output with file names; `None` decides from the
+    /// paths being searched.
+    pub with_filename: Option<bool>,
+    /// Order of the matching lines within each file; `None` keeps them in
+    /// the order they were found.
+    pub sort: Option<SortBy>,
+    /// Print JSON Lines events instead of grep-style output.
+    pub json: bool,
+    /// Replace matches with this template, expanding capture groups in
+    /// regex mode.
+    pub replace: Option<String>,
+    /// Write replacements back to the files instead of printing them.
+    pub in_place: bool,
+    /// With `in_place`, print a diff instead of writing.
+    pub dry_run: bool,
+    pub walker: Walker,
+    /// How many files to search at once.
+    pub threads: usize,
+    /// Keep output in the order files were found when `threads > 1`, which
+    /// is the default unless `--unordered` is given.
+    pub ordered: bool,
+    /// Decompress compressed files before searching them.
+    pub search_zip: bool,
+    /// Memory-map large files instead of reading them.
+    pub mmap: bool,
+    /// Search in a terminal UI as the query is typed.
+    pub interactive: bool,
+    /// Match only this field of each record, rather than whole lines.
+    pub field: Option<Field>,
+    /// Skip files that the index of their directory rules out.
+    pub index: bool,
+    /// Build the index of this directory instead of searching.
+    pub build_index: Option<PathBuf>,
+    /// Keep searching files as they grow.
+    pub follow: bool,
+    /// With `follow`, search what files already hold as well.
+    pub from_start: bool,
 }
 
 impl Config {
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+    /// Parse the command line, program name first. Anything that iterates
+    /// over arguments will do, such as `env::args()` itself.
+    pub fn build<I, T>(args: I) -> Result<Config, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut args = Args::try_parse_from(args)?;
+
+        // With -e, -f or --interactive there is no QUERY, so what clap took
+        // for one is really the first path.
+        let mut patterns = args.patterns;
+        if patterns.is_empty() && args.pattern_files.is_empty() && !args.interactive {
+            patterns.extend(args.query);
+        } else if let Some(path) = args.query {
+            args.paths.insert(0, PathBuf::from(path));
+        }
+        for file in &args.pattern_files {
+            let contents = fs::read_to_string(file).map_err(|e| {
+                Args::command().error(ErrorKind::Io, format!("{}: {e}", file.display()))
+            })?;
+            patterns.extend(contents.lines().map(String::from));
         }
 
-        let query = args[1].clone();
-        let file_path = args[2].clone();
+        let mode = if args.regex {
+            Mode::Regex
+        } else {
+            Mode::Literal
+        };
+
+        // An explicit flag always beats the environment variable.
+        let case = if args.ignore_case {
+            CaseMode::Insensitive
+        } else if args.no_ignore_case {
+            CaseMode::Sensitive
+        } else if args.smart_case {
+            CaseMode::Smart
+        } else if env::var("IGNORE_CASE").is_ok() {
+            CaseMode::Insensitive
+        } else {
+            CaseMode::Sensitive
+        };
+
+        let builder = Searcher::builder()
+            .mode(mode)
+            .case(case)
+            .word(args.word_regexp)
+            .line(args.line_regexp)
+            .fuzzy(args.fuzzy)
+            .invert_match(args.invert_match)
+            // -A and -B win over -C, as in grep.
+            .before_context(args.before_context.or(args.context).unwrap_or(0))
+            .after_context(args.after_context.or(args.context).unwrap_or(0))
+            .text(args.text);
+        let searcher = builder
+            .clone()
+            .patterns(patterns.iter().cloned())
+            .build()
+            .map_err(|e| Args::command().error(ErrorKind::ValueValidation, e))?;
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+        let field = match (args.csv_column, args.json_path) {
+            (Some(name), _) => Some(Field::CsvColumn(name)),
+            (None, Some(expr)) => {
+                Some(Field::JsonPath(JsonPath::parse(&expr).map_err(|e| {
+                    Args::command().error(ErrorKind::ValueValidation, e)
+                })?))
+            }
+            (None, None) => None,
+        };
+
+        let walker = Walker::new(&args.include, &args.exclude, args.hidden, args.no_ignore)
+            .map_err(|e| Args::command().error(ErrorKind::ValueValidation, e))?;
+
+        let with_filename = if args.with_filename {
+            Some(true)
+        } else if args.no_filename {
+            Some(false)
+        } else {
+            None
+        };
 
         Ok(Config {
-            query,
-            file_path,
-            ignore_case,
+            patterns,
+            paths: if args.paths.is_empty() {
+                vec![PathBuf::from(STDIN_PATH)]
+            } else {
+                args.paths
+            },
+            ignore_case: searcher.ignore_case(),
+            mode,
+            searcher,
+            builder,
+            line_number: args.line_number,
+            byte_offset: args.byte_offset,
+            count: args.count,
+            files_with_matches: args.files_with_matches,
+            files_without_match: args.files_without_match,
+            quiet: args.quiet,
+            color: args.color,
+            colors: env::var("MINIGREP_COLORS")
+                .map_or_else(|_| Colors::default(), |spec| Colors::parse(&spec)),
+            with_filename,
+            sort: args.sort,
+            json: args.json,
+            replace: args.replace,
+            in_place: args.in_place,
+            dry_run: args.dry_run,
+            walker,
+            // -q stops at the first match, so searching ahead would be
+            // wasted work.
+            threads: match args.threads {
+                _ if args.quiet => 1,
+                Some(threads) => usize::from(threads),
+                None => thread::available_parallelism().map_or(1, |n| n.get()),
+            },
+            ordered: !args.unordered,
+            search_zip: args.search_zip,
+            mmap: args.mmap,
+            interactive: args.interactive,
+            field,
+            index: args.index,
+            build_index: args
+                .command
+                .map(|Command::Index { command }| match command {
+                    IndexCommand::Build { dir } => dir,
+                }),
+            follow: args.follow,
+            from_start: args.from_start,
         })
     }
+
+    /// Whether to search files on several threads. Each file's output is
+    /// then held back until the file is done, so a lone file or standard
+    /// input, which may never end, is searched on this thread and printed
+    /// as it is found.
+    fn parallel(&self) -> bool {
+        let lone_file = matches!(&self.paths[..], [path] if !path.is_dir());
+        let stdin = self.paths.iter().any(|path| path == Path::new(STDIN_PATH));
+        self.threads > 1 && !lone_file && !stdin
+    }
+
+    fn use_color(&self) -> bool {
+        match self.color {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && env::var("TERM").map_or(true, |term| term != "dumb")
+            }
+        }
+    }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+/// Search every path in `config`, writing results to `out`, and return
+/// whether anything was selected: a line, or with `-L` a file. Errors with
+/// individual paths are reported on stderr and the search carries on; they
+/// make `run` fail at the end unless `-q` found a match.
+///
+/// With `--interactive` the terminal UI is run instead, and with
+/// `minigrep index build` the index is built; both return true unless they
+/// fail.
+pub fn run(config: Config, out: &mut impl Write) -> Result<bool, Box<dyn Error>> {
+    if let Some(dir) = &config.build_index {
+        let stats = index::build(dir)?;
+        writeln!(
+            out,
+            "indexed {} files in {} ({} read, {} removed)",
+            stats.files,
+            dir.display(),
+            stats.read,
+            stats.removed
+        )?;
+        return Ok(true);
+    }
+    if config.interactive {
+        interactive::run(config)?;
+        return Ok(true);
+    }
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
+    let printer = Printer {
+        with_filename: config
+            .with_filename
+            .unwrap_or(config.paths.len() > 1 || config.paths.iter().any(|path| path.is_dir())),
+        line_number: config.line_number,
+        byte_offset: config.byte_offset,
+        colors: config.use_color().then(|| config.colors.clone()),
+        json: config.json,
+    };
+    let mut summary = Summary::default();
+    // Following every file of a tree would take a thread for each.
+    if let Some(dir) = config
+        .paths
+        .iter()
+        .find(|path| config.follow && path.is_dir())
+    {
+        return Err(format!("{}: cannot follow a directory", dir.display()).into());
+    }
+    let filter = if config.index {
+        let query = index::Query::new(&config);
+        Some(index::Filter::new(&config.paths, query.as_ref())?)
     } else {
-        search(&config.query, &contents)
+        None
     };
+    let files = config
+        .walker
+        .files(&config.paths)
+        .filter(|file| match (&filter, file) {
+            (Some(filter), Ok(path)) => filter.may_match(path),
+            _ => true,
+        });
+
+    if config.follow {
+        let search = |path: &Path, stop: &AtomicBool, out: &mut follow::LineSender| {
+            follow_file(&config, &printer, path, stop, out)
+                .map_err(|e| format!("{}: {e}", path.display()))
+        };
+
+        follow::search_files(files, search, |output| {
+            match output {
+                Output::Lines(lines) => {
+                    out.write_all(&lines)?;
+                    out.flush()?;
+                }
+                Output::Done(Ok(count)) => summary.add_file(count),
+                Output::Done(Err(e)) => {
+                    eprintln!("minigrep: {e}");
+                    summary.errors += 1;
+                }
+            }
+            Ok(())
+        })?;
+    } else if !config.parallel() {
+        for file in files {
+            let result = file.and_then(|path| {
+                search_file(&config, &printer, &path, out)
+                    .map_err(|e| format!("{}: {e}", path.display()))
+            });
+
+            match result {
+                Ok(count) => summary.add_file(count),
+                Err(e) => {
+                    eprintln!("minigrep: {e}");
+                    summary.errors += 1;
+                }
+            }
+
+            if config.quiet && summary.matches > 0 {
+                break;
+            }
+        }
+    } else {
+        let search = |path: &Path| {
+            let mut buffer = Vec::new();
+            search_file(&config, &printer, path, &mut buffer)
+                .map(|count| (buffer, count))
+                .map_err(|e| format!("{}: {e}", path.display()))
+        };
+
+        parallel::search_files(files, config.threads, config.ordered, search, |result| {
+            let written = match result {
+                Ok((buffer, count)) => out
+                    .write_all(&buffer)
+                    .map(|()| count)
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e),
+            };
+
+            match written {
+                Ok(count) => summary.add_file(count),
+                Err(e) => {
+                    eprintln!("minigrep: {e}");
+                    summary.errors += 1;
+                }
+            }
+        });
+    }
+
+    if config.json {
+        json::summary(out, &summary)?;
+    }
 
-    for line in results {
-        println!("{line}");
+    if summary.errors > 0 && !(config.quiet && summary.matches > 0) {
+        return Err("some paths could not be searched".into());
     }
 
-    Ok(())
+    Ok(if config.files_without_match {
+        summary.files_matched < summary.files_searched
+    } else {
+        summary.matches > 0
+    })
 }
 
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let mut results = Vec::new();
+fn search_file(
+    config: &Config,
+    printer: &Printer,
+    path: &Path,
+    out: &mut impl Write,
+) -> io::Result<u64> {
+    if config.in_place {
+        if path == Path::new(STDIN_PATH) {
+            return Err(io::Error::other("cannot edit standard input in place"));
+        }
+        replace::edit_file(config, path, out)
+    } else if path == Path::new(STDIN_PATH) {
+        let stdin = io::stdin().lock();
+        search_stream(config, printer, path, "(standard input)", stdin, out)
+    } else {
+        let file = File::open(path)?;
+        let name = path.display().to_string();
 
-    for line in contents.lines() {
-        if line.contains(query) {
-            results.push(line);
+        let map = if config.mmap { map(&file)? } else { None };
+        match map {
+            Some(map) if !config.search_zip => search_slice(config, printer, &name, &map, out),
+            Some(map) => search_stream(config, printer, path, &name, &map[..], out),
+            None => {
+                let reader = BufReader::with_capacity(READ_BUFFER_LEN, file);
+                search_stream(config, printer, path, &name, reader, out)
+            }
         }
     }
+}
+
+/// Search `path` with `--follow`, which never finishes unless `stop` is
+/// set. There is nothing to follow past the end of standard input, so that
+/// is searched as usual.
+fn follow_file(
+    config: &Config,
+    printer: &Printer,
+    path: &Path,
+    stop: &AtomicBool,
+    out: &mut impl Write,
+) -> io::Result<u64> {
+    if path == Path::new(STDIN_PATH) {
+        let stdin = io::stdin().lock();
+        return search_reader(config, printer, "(standard input)", stdin, out);
+    }
 
-    results
+    let follower = Follower::open(path, config.from_start, stop)?;
+    let name = path.display().to_string();
+    search_reader(config, printer, &name, BufReader::new(follower), out)
 }
 
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    let mut results = Vec::new();
+/// Search `reader`, decompressing it first with `-z`. Results are reported
+/// under `name`, which for a compressed file is its own name.
+fn search_stream(
+    config: &Config,
+    printer: &Printer,
+    path: &Path,
+    name: &str,
+    reader: impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<u64> {
+    if config.search_zip {
+        search_reader(
+            config,
+            printer,
+            name,
+            decompress::reader(path, reader)?,
+            out,
+        )
+    } else {
+        search_reader(config, printer, name, reader, out)
+    }
+}
 
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
-        }
+/// Map `file` into memory for `--mmap` if it is a regular file big enough
+/// for that to pay off. Anything that cannot be mapped is read as usual
+/// instead.
+fn map(file: &File) -> io::Result<Option<Mmap>> {
+    let metadata = file.metadata()?;
+    if !metadata.is_file() || metadata.len() < MMAP_MIN_LEN {
+        return Ok(None);
     }
 
-    results
+    // SAFETY: the map is only read, and only while the file is searched.
+    // If another process truncates the file in the meantime, reading the
+    // lost pages raises SIGBUS and kills us, which is why mapping is only
+    // done when asked for.
+    Ok(unsafe { Mmap::map(file) }.ok())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_support::args;
+    use searcher::{search_matches, MatchOptions, Matcher};
+    use std::io::Read;
+
+    fn lines_matching<'a>(matcher: &dyn Matcher, contents: &'a str) -> Vec<&'a str> {
+        search_matches(matcher, contents)
+            .into_iter()
+            .map(|m| m.line)
+            .collect()
+    }
 
     #[test]
     fn case_sensitive() {
@@ -98,4 +529,512 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn regex_mode() {
+        let matcher = Mode::Regex
+            .compile(&[r"^\w+:$|^D".to_string()], MatchOptions::default())
+            .unwrap();
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(
+            vec!["Rust:", "Duct tape."],
+            lines_matching(matcher.as_ref(), contents)
+        );
+    }
+
+    #[test]
+    fn literal_mode_does_not_interpret_metacharacters() {
+        let matcher = Mode::Literal
+            .compile(&["e.".to_string()], MatchOptions::default())
+            .unwrap();
+        let contents = "\
+Pick three.
+Duct tape.
+every day";
+
+        assert_eq!(
+            vec!["Pick three.", "Duct tape."],
+            lines_matching(matcher.as_ref(), contents)
+        );
+    }
+
+    #[test]
+    fn build_selects_mode_from_flags() {
+        let config = Config::build(args(&["minigrep", "-E", "a+", "poem.txt"])).unwrap();
+        assert_eq!(Mode::Regex, config.mode);
+
+        let config = Config::build(args(&["minigrep", "-E", "-F", "a+", "poem.txt"])).unwrap();
+        assert_eq!(Mode::Literal, config.mode);
+
+        let config = Config::build(args(&["minigrep", "--", "-E", "poem.txt"])).unwrap();
+        assert_eq!(vec!["-E"], config.patterns);
+    }
+
+    #[test]
+    fn build_reports_invalid_regex() {
+        let err = Config::build(args(&["minigrep", "--regex", "(unclosed", "poem.txt"]))
+            .err()
+            .unwrap();
+
+        assert_eq!(ErrorKind::ValueValidation, err.kind());
+        assert!(err.to_string().contains("invalid regular expression"));
+    }
+
+    #[test]
+    fn build_parses_output_flags() {
+        let config = Config::build(args(&[
+            "minigrep",
+            "-vnc",
+            "-l",
+            "--color=never",
+            "to",
+            "poem.txt",
+        ]))
+        .unwrap();
+
+        assert!(config.searcher.invert_match());
+        assert!(config.line_number);
+        assert!(config.count);
+        assert!(config.files_with_matches);
+        assert_eq!(ColorChoice::Never, config.color);
+    }
+
+    #[test]
+    fn ignore_case_flags_take_precedence_in_order() {
+        let config = Config::build(args(&["minigrep", "-i", "to", "poem.txt"])).unwrap();
+        assert!(config.ignore_case);
+
+        let config = Config::build(args(&[
+            "minigrep",
+            "-i",
+            "--no-ignore-case",
+            "to",
+            "poem.txt",
+        ]))
+        .unwrap();
+        assert!(!config.ignore_case);
+    }
+
+    #[test]
+    fn smart_case_depends_on_the_query() {
+        let config = Config::build(args(&["minigrep", "-S", "to", "poem.txt"])).unwrap();
+        assert!(config.ignore_case);
+
+        let config = Config::build(args(&["minigrep", "-S", "To", "poem.txt"])).unwrap();
+        assert!(!config.ignore_case);
+
+        let config = Config::build(args(&["minigrep", "-S", "-i", "To", "poem.txt"])).unwrap();
+        assert!(config.ignore_case);
+    }
+
+    #[test]
+    fn build_collects_patterns_from_flags_and_files() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(b"one\ntwo\n").unwrap();
+        let file = file.path().to_str().unwrap();
+
+        let config = Config::build(args(&[
+            "minigrep", "-e", "to", "-f", file, "-e", "be", "poem.txt", "src",
+        ]))
+        .unwrap();
+        assert_eq!(vec!["to", "be", "one", "two"], config.patterns);
+        assert_eq!(
+            vec![PathBuf::from("poem.txt"), PathBuf::from("src")],
+            config.paths
+        );
+
+        let err = Config::build(args(&["minigrep", "-f", "no-such-file"]))
+            .err()
+            .unwrap();
+        assert_eq!(ErrorKind::Io, err.kind());
+    }
+
+    #[test]
+    fn several_patterns_select_lines_matching_any() {
+        assert_eq!(
+            "one\nthree\n",
+            search_bytes(&["minigrep", "-e", "ne", "-e", "hr"], b"one\ntwo\nthree\n")
+        );
+    }
+
+    #[test]
+    fn build_accepts_several_paths() {
+        let config = Config::build(args(&["minigrep", "to", "poem.txt", "src"])).unwrap();
+        assert_eq!(
+            vec![PathBuf::from("poem.txt"), PathBuf::from("src")],
+            config.paths
+        );
+        assert_eq!(None, config.with_filename);
+
+        assert!(Config::build(args(&["minigrep"])).is_err());
+        assert!(Config::build(args(&["minigrep", "--include", "[", "to", "src"])).is_err());
+    }
+
+    fn config(list: &[&str]) -> (Config, Printer) {
+        let config = Config::build(args(list)).unwrap();
+        let printer = Printer {
+            with_filename: false,
+            line_number: config.line_number,
+            byte_offset: config.byte_offset,
+            colors: None,
+            json: config.json,
+        };
+        (config, printer)
+    }
+
+    fn search_bytes(list: &[&str], input: &[u8]) -> String {
+        let (config, printer) = config(list);
+        let mut out = Vec::new();
+        search_reader(&config, &printer, "input", input, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn build_reads_stdin_without_paths() {
+        let (config, _) = config(&["minigrep", "to"]);
+
+        assert_eq!(vec![PathBuf::from("-")], config.paths);
+    }
+
+    #[test]
+    fn streams_lines_and_tolerates_invalid_utf8() {
+        assert_eq!(
+            "2:caf\u{fffd} crème\n",
+            search_bytes(&["minigrep", "-n", "crème"], b"tea\ncaf\xe9 cr\xc3\xa8me\n")
+        );
+    }
+
+    #[test]
+    fn reports_binary_files_instead_of_printing_them() {
+        let input = b"ELF\0\x01\nmatch here\n";
+
+        assert_eq!(
+            "Binary file input matches\n",
+            search_bytes(&["minigrep", "match"], input)
+        );
+        assert_eq!("1\n", search_bytes(&["minigrep", "-c", "match"], input));
+        assert_eq!(
+            "match here\n",
+            search_bytes(&["minigrep", "-a", "match"], input)
+        );
+        assert_eq!("", search_bytes(&["minigrep", "nothing"], input));
+    }
+
+    #[test]
+    fn prefixes_byte_offsets() {
+        assert_eq!(
+            "4:two\n",
+            search_bytes(&["minigrep", "-b", "two"], b"one\ntwo\n")
+        );
+    }
+
+    #[test]
+    fn json_reports_each_file_as_events() {
+        let out = search_bytes(&["minigrep", "--json", "-A1", "two"], b"one\ntwo\nthree\n");
+
+        assert_eq!(
+            r#"{"type":"begin","path":"input"}
+{"type":"match","path":"input","line_number":2,"byte_offset":4,"line":"two","submatches":[{"text":"two","start":0,"end":3}]}
+{"type":"context","path":"input","line_number":3,"byte_offset":8,"line":"three"}
+{"type":"end","path":"input","matches":1,"binary":false}
+"#,
+            out
+        );
+        assert!(Config::build(args(&["minigrep", "--json", "-c", "to"])).is_err());
+    }
+
+    #[test]
+    fn json_ends_a_file_that_fails_partway() {
+        let (config, printer) = config(&["minigrep", "--json", "two"]);
+        let failing = b"one\ntwo\n".chain(FailingReader);
+        let mut out = Vec::new();
+
+        assert!(search_reader(&config, &printer, "input", failing, &mut out).is_err());
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with(r#"{"type":"begin","path":"input"}"#));
+        assert!(out.ends_with(
+            r#"{"type":"end","path":"input","matches":1,"binary":false}
+"#
+        ));
+    }
+
+    #[test]
+    fn json_ends_a_file_without_the_csv_column() {
+        let (config, printer) = config(&["minigrep", "--json", "--csv-column=user", "x"]);
+        let mut out = Vec::new();
+
+        assert!(search_reader(&config, &printer, "input", &b"id\n1\n"[..], &mut out).is_err());
+        assert_eq!(
+            r#"{"type":"begin","path":"input"}
+{"type":"end","path":"input","matches":0,"binary":false}
+"#,
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    struct FailingReader;
+
+    impl Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+
+    impl BufRead for FailingReader {
+        fn fill_buf(&mut self) -> io::Result<&[u8]> {
+            Err(io::Error::other("disk on fire"))
+        }
+
+        fn consume(&mut self, _: usize) {}
+    }
+
+    #[test]
+    fn replace_prints_substituted_lines() {
+        assert_eq!(
+            "2:x-1\n",
+            search_bytes(
+                &["minigrep", "-n", "-E", "--replace=$2-$1", r"(\d)-(\w)"],
+                b"none\n1-x\n"
+            )
+        );
+        assert!(Config::build(args(&["minigrep", "--in-place", "to", "poem.txt"])).is_err());
+    }
+
+    #[test]
+    fn fuzzy_matches_sort_by_distance() {
+        let input = b"recieve\nnothing\nreceive\nrecive\nrcv\n";
+
+        assert_eq!(
+            "1:recieve\n3:receive\n4:recive\n",
+            search_bytes(&["minigrep", "-n", "--fuzzy=2", "receive"], input)
+        );
+        assert_eq!(
+            "3:receive\n4:recive\n1:recieve\n",
+            search_bytes(
+                &["minigrep", "-n", "--fuzzy=2", "--sort=score", "receive"],
+                input
+            )
+        );
+        assert!(Config::build(args(&["minigrep", "--sort=score", "to"])).is_err());
+    }
+
+    #[test]
+    fn context_flags_fall_back_to_dash_c() {
+        let (config, _) = config(&["minigrep", "-C", "2", "-A", "1", "to"]);
+
+        assert_eq!(2, config.searcher.before_context());
+        assert_eq!(1, config.searcher.after_context());
+    }
+
+    #[test]
+    fn prints_context_with_separators_between_groups() {
+        let input = b"one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\n";
+
+        assert_eq!(
+            "1-one\n2:two\n3-three\n--\n6-six\n7:seven\n8-eight\n",
+            search_bytes(&["minigrep", "-n", "-C1", "-E", "^(two|seven)$"], input)
+        );
+        assert_eq!(
+            "2:two\n3-three\n4:four\n5-five\n",
+            search_bytes(&["minigrep", "-n", "-A1", "-E", "^(two|four)$"], input)
+        );
+    }
+
+    #[test]
+    fn search_zip_decompresses_and_keeps_the_file_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log.gz");
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(&path).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder.write_all(b"one\ntwo\n").unwrap();
+        encoder.finish().unwrap();
+        let name = path.to_str().unwrap();
+
+        let (config, mut printer) = config(&["minigrep", "-z", "two", name]);
+        printer.with_filename = true;
+        let mut out = Vec::new();
+        search_file(&config, &printer, &path, &mut out).unwrap();
+
+        assert_eq!(format!("{name}:two\n"), String::from_utf8(out).unwrap());
+    }
+
+    #[test]
+    fn search_zip_cannot_edit_in_place() {
+        assert!(
+            Config::build(args(&["minigrep", "-z", "--replace=x", "--in-place", "t"])).is_err()
+        );
+    }
+
+    #[test]
+    fn maps_large_files_with_the_same_results() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut contents = "filler\n".repeat(MMAP_MIN_LEN as usize);
+        contents += "needle\r\nfiller\n";
+        file.write_all(contents.as_bytes()).unwrap();
+        assert!(map(file.as_file()).unwrap().is_some());
+
+        assert!(!config(&["minigrep", "needle"]).0.mmap);
+        let (config, printer) = config(&["minigrep", "--mmap", "-nb", "needle"]);
+        let mut out = Vec::new();
+        search_file(&config, &printer, file.path(), &mut out).unwrap();
+
+        assert_eq!(
+            search_bytes(&["minigrep", "-nb", "needle"], contents.as_bytes()),
+            String::from_utf8(out).unwrap()
+        );
+    }
+
+    #[test]
+    fn lists_files_with_and_without_matches() {
+        assert_eq!(
+            "input\n",
+            search_bytes(&["minigrep", "-l", "two"], b"one\ntwo\n")
+        );
+        assert_eq!("", search_bytes(&["minigrep", "-L", "two"], b"one\ntwo\n"));
+        assert_eq!(
+            "input\n",
+            search_bytes(&["minigrep", "-L", "six"], b"one\ntwo\n")
+        );
+
+        let (config, _) = config(&["minigrep", "-l", "-L", "two"]);
+        assert!(config.files_without_match && !config.files_with_matches);
+    }
+
+    #[test]
+    fn quiet_prints_nothing() {
+        assert_eq!("", search_bytes(&["minigrep", "-q", "two"], b"one\ntwo\n"));
+        assert_eq!("", search_bytes(&["minigrep", "-qc", "two"], b"one\ntwo\n"));
+        assert_eq!("", search_bytes(&["minigrep", "-q", "two"], b"\0two\n"));
+        assert_eq!(1, config(&["minigrep", "-q", "-j4", "two"]).0.threads);
+    }
+
+    #[test]
+    fn streams_a_lone_file_or_stdin_on_one_thread() {
+        let dir = tempfile::tempdir().unwrap();
+        let dir = dir.path().to_str().unwrap();
+
+        assert!(config(&["minigrep", "-j4", "two", dir]).0.parallel());
+        assert!(config(&["minigrep", "-j4", "two", "a", "b"]).0.parallel());
+        assert!(!config(&["minigrep", "-j4", "two", "a"]).0.parallel());
+        assert!(!config(&["minigrep", "-j4", "two"]).0.parallel());
+        assert!(!config(&["minigrep", "-j4", "two", "a", "-"]).0.parallel());
+        assert!(!config(&["minigrep", "-j1", "two", dir]).0.parallel());
+    }
+
+    #[test]
+    fn output_is_ordered_unless_asked_not_to_be() {
+        assert!(config(&["minigrep", "-j4", "two"]).0.ordered);
+        assert!(!config(&["minigrep", "-j4", "--unordered", "two"]).0.ordered);
+    }
+
+    #[test]
+    fn run_reports_whether_anything_was_selected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("input");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        let name = path.to_str().unwrap();
+        let missing = dir.path().join("missing");
+        let missing = missing.to_str().unwrap();
+        let run_with = |list: &[&str]| {
+            let mut out = Vec::new();
+            run(Config::build(args(list)).unwrap(), &mut out).map_err(|e| e.to_string())
+        };
+
+        assert_eq!(Ok(true), run_with(&["minigrep", "-c", "two", name]));
+        assert_eq!(Ok(false), run_with(&["minigrep", "six", name]));
+        assert_eq!(Ok(true), run_with(&["minigrep", "-L", "six", name]));
+        assert_eq!(Ok(false), run_with(&["minigrep", "-L", "two", name]));
+        assert!(run_with(&["minigrep", "two", name, missing]).is_err());
+        assert_eq!(
+            Ok(true),
+            run_with(&["minigrep", "-q", "two", missing, name])
+        );
+    }
+
+    #[test]
+    fn matches_fields_of_structured_records() {
+        let csv = b"id,user\n1,ann\n2,joanna\n";
+        assert_eq!(
+            "3:2,joanna\n",
+            search_bytes(&["minigrep", "-n", "--csv-column", "user", "jo"], csv)
+        );
+        assert_eq!(
+            "1\n",
+            search_bytes(&["minigrep", "-c", "--csv-column=id", "-x", "1"], csv)
+        );
+
+        let json = b"{\"user\":\"ann\"}\n{\"user\":\"joanna\"}\n";
+        assert_eq!(
+            "{\"user\":\"ann\"}\n",
+            search_bytes(&["minigrep", "--json-path=.user", "-v", "jo"], json)
+        );
+
+        assert!(Config::build(args(&["minigrep", "--json-path=a[", "x"])).is_err());
+        assert!(Config::build(args(&["minigrep", "--csv-column=a", "-C1", "x"])).is_err());
+    }
+
+    #[test]
+    fn follow_only_goes_with_output_printed_as_it_is_found() {
+        let (following, _) = config(&["minigrep", "--follow", "-n", "error", "app.log"]);
+        assert!(following.follow);
+        assert!(!following.from_start);
+
+        for flag in ["-c", "-l", "-q", "-z", "--index"] {
+            let list = ["minigrep", "--follow", flag, "error", "app.log"];
+            assert!(Config::build(args(&list)).is_err(), "{flag}");
+        }
+        assert!(Config::build(args(&["minigrep", "--from-start", "error", "app.log"])).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let list = [
+            "minigrep",
+            "--follow",
+            "error",
+            dir.path().to_str().unwrap(),
+        ];
+        let err = run(config(&list).0, &mut Vec::new()).unwrap_err();
+        assert!(
+            err.to_string().ends_with("cannot follow a directory"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn run_builds_the_index() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.txt"), "hello\n").unwrap();
+        let (config, _) = config(&["minigrep", "index", "build", dir.path().to_str().unwrap()]);
+
+        let mut out = Vec::new();
+        assert!(run(config, &mut out).unwrap());
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("indexed 1 files in "));
+        assert!(dir.path().join(index::INDEX_FILE).exists());
+    }
+
+    #[test]
+    fn index_build_is_a_subcommand() {
+        let (building, _) = config(&["minigrep", "index", "build", "src"]);
+        assert_eq!(Some(PathBuf::from("src")), building.build_index);
+        let (here, _) = config(&["minigrep", "index", "build"]);
+        assert_eq!(Some(PathBuf::from(".")), here.build_index);
+
+        let (searching, _) = config(&["minigrep", "-e", "index", "build"]);
+        assert_eq!(None, searching.build_index);
+        assert_eq!(vec!["index".to_string()], searching.patterns);
+        assert_eq!(vec![PathBuf::from("build")], searching.paths);
+
+        // Once there are options, it can only be a search.
+        let (searching, _) = config(&["minigrep", "-i", "index", "build"]);
+        assert_eq!(None, searching.build_index);
+        assert!(Config::build(args(&["minigrep", "index", "build", ".", "x"])).is_err());
+    }
 }
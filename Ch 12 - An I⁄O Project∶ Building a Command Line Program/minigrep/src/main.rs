@@ -1,18 +1,25 @@
 use std::env;
+use std::io::{self, Write};
 use std::process;
 
 use minigrep::Config;
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
+    let config = Config::build(env::args()).unwrap_or_else(|err| err.exit());
 
-    let config = Config::build(&args).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {err}");
-        process::exit(1);
-    });
+    // Exit statuses follow grep: 0 if something matched, 1 if nothing did
+    // and 2 on errors.
+    let mut stdout = io::stdout().lock();
+    let code = match minigrep::run(config, &mut stdout) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            2
+        }
+    };
 
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {e}");
-        process::exit(1);
-    }
+    // `process::exit` skips destructors, so flush what is buffered first.
+    let _ = stdout.flush();
+    process::exit(code);
 }
@@ -0,0 +1,177 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{mpsc, Mutex},
+    thread,
+};
+
+/// Search `files` on `threads` worker threads.
+///
+/// Like `hello::ThreadPool`, the workers share one job queue behind a mutex.
+/// Each file is searched into its own result, typically a buffer of output,
+/// and `emit` receives every result whole, so one file's output is never
+/// interleaved with another's. With `ordered`, results are handed over in
+/// the order `files` produced them; otherwise in the order the searches
+/// finish.
+///
+/// At most `4 * threads` files are in flight between being handed to a
+/// worker and their result being emitted, so a slow file holds back only
+/// that many finished results rather than the whole rest of the search.
+pub fn search_files<I, T, S, E>(files: I, threads: usize, ordered: bool, search: S, mut emit: E)
+where
+    I: Iterator<Item = Result<PathBuf, String>> + Send,
+    T: Send,
+    S: Fn(&Path) -> Result<T, String> + Sync,
+    E: FnMut(Result<T, String>),
+{
+    let threads = threads.max(1);
+    let window = 4 * threads;
+    let (job_sender, job_receiver) = mpsc::sync_channel(threads);
+    let job_receiver = Mutex::new(job_receiver);
+    let (result_sender, result_receiver) = mpsc::sync_channel(window);
+
+    // One permit per file in flight: taken before a file is handed out and
+    // given back once its result has been emitted.
+    let (permit_sender, permit_receiver) = mpsc::sync_channel(window);
+    for _ in 0..window {
+        permit_sender.send(()).unwrap();
+    }
+
+    thread::scope(|scope| {
+        scope.spawn(move || {
+            for job in files.enumerate() {
+                if permit_receiver.recv().is_err() || job_sender.send(job).is_err() {
+                    break;
+                }
+            }
+        });
+
+        for _ in 0..threads {
+            let job_receiver = &job_receiver;
+            let result_sender = result_sender.clone();
+            let search = &search;
+
+            scope.spawn(move || loop {
+                let job = job_receiver.lock().unwrap().recv();
+                let Ok((index, file)) = job else {
+                    break;
+                };
+
+                let result = file.and_then(|path| search(&path));
+                if result_sender.send((index, result)).is_err() {
+                    break;
+                }
+            });
+        }
+
+        // Only the workers hold senders now, so the loops below end once
+        // they have all finished.
+        drop(result_sender);
+
+        if ordered {
+            let mut pending = BTreeMap::new();
+            let mut next = 0;
+
+            for (index, result) in result_receiver {
+                pending.insert(index, result);
+                while let Some(result) = pending.remove(&next) {
+                    emit(result);
+                    let _ = permit_sender.send(());
+                    next += 1;
+                }
+            }
+        } else {
+            for (_, result) in result_receiver {
+                emit(result);
+                let _ = permit_sender.send(());
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    fn files(count: usize) -> impl Iterator<Item = Result<PathBuf, String>> + Send {
+        (0..count).map(|i| {
+            if i == 3 {
+                Err("3: unreadable".to_string())
+            } else {
+                Ok(PathBuf::from(i.to_string()))
+            }
+        })
+    }
+
+    /// Earlier files take longer, so unordered output comes back shuffled.
+    fn slow_search(path: &Path) -> Result<Vec<u8>, String> {
+        let i: u64 = path.to_str().unwrap().parse().unwrap();
+        thread::sleep(Duration::from_millis(5 * (10 - i)));
+        Ok(format!("{i}a\n{i}b\n").into_bytes())
+    }
+
+    #[test]
+    fn ordered_output_follows_input_order() {
+        let mut out = Vec::new();
+        search_files(files(10), 4, true, slow_search, |result| {
+            out.push(result.unwrap_or_else(|e| e.into_bytes()))
+        });
+
+        let expected: Vec<Vec<u8>> = (0..10)
+            .map(|i| match i {
+                3 => b"3: unreadable".to_vec(),
+                i => format!("{i}a\n{i}b\n").into_bytes(),
+            })
+            .collect();
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn a_slow_file_holds_back_a_bounded_number_of_results() {
+        let searched = AtomicUsize::new(0);
+        let mut emitted = 0;
+        search_files(
+            (0..100).map(|i| Ok(PathBuf::from(i.to_string()))),
+            2,
+            true,
+            |path| {
+                if path == Path::new("0") {
+                    thread::sleep(Duration::from_millis(100));
+                    Ok(searched.load(Ordering::SeqCst))
+                } else {
+                    searched.fetch_add(1, Ordering::SeqCst);
+                    Ok(0)
+                }
+            },
+            |result| {
+                // File 0 was searched while at most the window was handed out.
+                if emitted == 0 {
+                    assert!(result.unwrap() < 8);
+                }
+                emitted += 1;
+            },
+        );
+        assert_eq!(100, emitted);
+    }
+
+    #[test]
+    fn unordered_output_keeps_each_file_together() {
+        let mut out = Vec::new();
+        search_files(files(10), 4, false, slow_search, |result| {
+            if let Ok(buffer) = result {
+                out.push(String::from_utf8(buffer).unwrap())
+            }
+        });
+
+        assert_eq!(9, out.len());
+        for block in out {
+            let lines: Vec<&str> = block.lines().collect();
+            assert_eq!(
+                lines[0].trim_end_matches('a'),
+                lines[1].trim_end_matches('b')
+            );
+        }
+    }
+}
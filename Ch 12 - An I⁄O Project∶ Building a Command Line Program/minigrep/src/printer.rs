@@ -0,0 +1,291 @@
+use crate::json;
+use searcher::Match;
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::ops::Range;
+
+const RESET_COLOR: &str = "\x1b[0m";
+
+/// SGR sequences for each part of the output, like grep's `GREP_COLORS`.
+///
+/// `Colors::parse` reads the same `key=value:key=value` format as grep, for
+/// the keys `mt` (or `ms`) for matched text, `fn` for file names, `ln` for
+/// line numbers, `bn` for byte offsets and `se` for separators. An empty
+/// value turns colouring of that part off; unknown keys and malformed
+/// values are ignored, as grep does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Colors {
+    pub matched: String,
+    pub file_name: String,
+    pub line_number: String,
+    pub byte_offset: String,
+    pub separator: String,
+}
+
+impl Default for Colors {
+    /// grep's defaults.
+    fn default() -> Colors {
+        Colors {
+            matched: "01;31".to_string(),
+            file_name: "35".to_string(),
+            line_number: "32".to_string(),
+            byte_offset: "32".to_string(),
+            separator: "36".to_string(),
+        }
+    }
+}
+
+impl Colors {
+    pub fn parse(spec: &str) -> Colors {
+        let mut colors = Colors::default();
+
+        for entry in spec.split(':') {
+            let Some((key, value)) = entry.split_once('=') else {
+                continue;
+            };
+            if !value.bytes().all(|b| b.is_ascii_digit() || b == b';') {
+                continue;
+            }
+
+            let field = match key {
+                "mt" | "ms" => &mut colors.matched,
+                "fn" => &mut colors.file_name,
+                "ln" => &mut colors.line_number,
+                "bn" => &mut colors.byte_offset,
+                "se" => &mut colors.separator,
+                _ => continue,
+            };
+            *field = value.to_string();
+        }
+
+        colors
+    }
+}
+
+/// Writes search results in grep's format.
+///
+/// Matching lines use `:` between the file name, line number, byte offset
+/// and text; context lines use `-`. Without `colors`, nothing is coloured.
+/// With `json`, everything is written as the events described in
+/// [`crate::json`] instead, and the other settings are ignored.
+pub struct Printer {
+    pub with_filename: bool,
+    pub line_number: bool,
+    pub byte_offset: bool,
+    pub colors: Option<Colors>,
+    pub json: bool,
+}
+
+impl Printer {
+    /// Called before searching each input.
+    pub fn begin(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        if self.json {
+            return json::begin(out, name);
+        }
+        Ok(())
+    }
+
+    /// Called after searching each input, with the number of lines selected.
+    pub fn end(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        count: u64,
+        binary: bool,
+    ) -> io::Result<()> {
+        if self.json {
+            return json::end(out, name, count, binary);
+        }
+        Ok(())
+    }
+
+    pub fn matched(&self, out: &mut impl Write, name: &str, m: &Match) -> io::Result<()> {
+        if self.json {
+            return json::matched(out, name, m);
+        }
+        self.prefix(out, name, m.line_number, m.byte_offset, ':')?;
+
+        match &self.colors {
+            Some(colors) => writeln!(out, "{}", highlight(m.line, &m.spans, &colors.matched)),
+            None => writeln!(out, "{}", m.line),
+        }
+    }
+
+    pub fn context(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        line_number: u64,
+        byte_offset: u64,
+        line: &str,
+    ) -> io::Result<()> {
+        if self.json {
+            return json::context(out, name, line_number, byte_offset, line);
+        }
+        self.prefix(out, name, line_number, byte_offset, '-')?;
+        writeln!(out, "{line}")
+    }
+
+    /// Marks a gap between two groups of context.
+    pub fn separator(&self, out: &mut impl Write) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
+        self.paint(out, "--", |colors| &colors.separator)?;
+        writeln!(out)
+    }
+
+    pub fn count(&self, out: &mut impl Write, name: &str, count: u64) -> io::Result<()> {
+        if self.with_filename {
+            self.paint(out, name, |colors| &colors.file_name)?;
+            self.paint(out, ':', |colors| &colors.separator)?;
+        }
+        writeln!(out, "{count}")
+    }
+
+    pub fn file_name(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        self.paint(out, name, |colors| &colors.file_name)?;
+        writeln!(out)
+    }
+
+    pub fn binary_match(&self, out: &mut impl Write, name: &str) -> io::Result<()> {
+        if self.json {
+            return Ok(());
+        }
+        writeln!(out, "Binary file {name} matches")
+    }
+
+    fn prefix(
+        &self,
+        out: &mut impl Write,
+        name: &str,
+        line_number: u64,
+        byte_offset: u64,
+        separator: char,
+    ) -> io::Result<()> {
+        if self.with_filename {
+            self.paint(out, name, |colors| &colors.file_name)?;
+            self.paint(out, separator, |colors| &colors.separator)?;
+        }
+        if self.line_number {
+            self.paint(out, line_number, |colors| &colors.line_number)?;
+            self.paint(out, separator, |colors| &colors.separator)?;
+        }
+        if self.byte_offset {
+            self.paint(out, byte_offset, |colors| &colors.byte_offset)?;
+            self.paint(out, separator, |colors| &colors.separator)?;
+        }
+        Ok(())
+    }
+
+    /// Write `text` in the colour `pick` chooses, if colouring is on.
+    fn paint(
+        &self,
+        out: &mut impl Write,
+        text: impl Display,
+        pick: impl Fn(&Colors) -> &String,
+    ) -> io::Result<()> {
+        match self.colors.as_ref().map(pick) {
+            Some(sgr) if !sgr.is_empty() => write!(out, "\x1b[{sgr}m{text}{RESET_COLOR}"),
+            _ => write!(out, "{text}"),
+        }
+    }
+}
+
+fn highlight(line: &str, spans: &[Range<usize>], sgr: &str) -> String {
+    if sgr.is_empty() {
+        return line.to_string();
+    }
+
+    let mut out = String::with_capacity(line.len());
+    let mut last = 0;
+
+    for span in spans.iter().filter(|span| !span.is_empty()) {
+        out.push_str(&line[last..span.start]);
+        out.push_str(&format!("\x1b[{sgr}m"));
+        out.push_str(&line[span.clone()]);
+        out.push_str(RESET_COLOR);
+        last = span.end;
+    }
+
+    out.push_str(&line[last..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn printed(printer: &Printer, m: &Match) -> String {
+        let mut out = Vec::new();
+        printer.matched(&mut out, "poem.txt", m).unwrap();
+        printer
+            .context(&mut out, "poem.txt", 3, 40, "context")
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn bog_to() -> Match<'static> {
+        Match {
+            line_number: 2,
+            byte_offset: 25,
+            line: "bog to",
+            spans: vec![1..2, 5..6],
+        }
+    }
+
+    #[test]
+    fn prefixes_name_line_number_and_offset() {
+        let printer = Printer {
+            with_filename: true,
+            line_number: true,
+            byte_offset: true,
+            colors: None,
+            json: false,
+        };
+
+        assert_eq!(
+            "poem.txt:2:25:bog to\npoem.txt-3-40-context\n",
+            printed(&printer, &bog_to())
+        );
+    }
+
+    #[test]
+    fn highlights_every_match() {
+        assert_eq!(
+            "b\x1b[01;31mo\x1b[0mg t\x1b[01;31mo\x1b[0m",
+            highlight("bog to", &[1..2, 5..6], "01;31")
+        );
+    }
+
+    #[test]
+    fn colours_names_numbers_and_separators() {
+        let printer = Printer {
+            with_filename: true,
+            line_number: true,
+            byte_offset: false,
+            colors: Some(Colors::parse("fn=34:ln=:mt=7")),
+            json: false,
+        };
+
+        assert_eq!(
+            "\x1b[34mpoem.txt\x1b[0m\x1b[36m:\x1b[0m2\x1b[36m:\x1b[0m\
+             b\x1b[7mo\x1b[0mg t\x1b[7mo\x1b[0m\n\
+             \x1b[34mpoem.txt\x1b[0m\x1b[36m-\x1b[0m3\x1b[36m-\x1b[0mcontext\n",
+            printed(&printer, &bog_to())
+        );
+    }
+
+    #[test]
+    fn parse_ignores_unknown_keys_and_bad_values() {
+        let colors = Colors::parse("xx=1:fn=red:se=33:nonsense");
+
+        assert_eq!(
+            Colors {
+                separator: "33".to_string(),
+                ..Colors::default()
+            },
+            colors
+        );
+    }
+}
@@ -0,0 +1,300 @@
+use crate::Config;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use tempfile::NamedTempFile;
+
+/// Apply `--replace` to the file at `path` for `--in-place`, returning the
+/// number of lines that matched, whether or not replacing changed them.
+///
+/// The new contents are written to a temporary file next to the original,
+/// which is then renamed over it, so the file is never left half written.
+/// A symlink is followed, and the file it points to is edited. With
+/// `--dry-run` nothing is written; a unified diff of the changes goes to
+/// `out` instead. Binary files are left alone unless `-a` is given.
+///
+/// Files need not be UTF-8: each line is matched as `--replace` alone would
+/// match it, with invalid bytes read as U+FFFD, and every byte outside a
+/// match is kept exactly as it was.
+pub fn edit_file(config: &Config, path: &Path, out: &mut impl Write) -> io::Result<u64> {
+    let Some(template) = &config.replace else {
+        return Ok(0);
+    };
+
+    let contents = fs::read(path)?;
+    if !config.searcher.text() && contents.contains(&0) {
+        return Ok(0);
+    }
+
+    let mut edited = Vec::with_capacity(contents.len());
+    let mut changes = Vec::new();
+    let mut matched = 0;
+
+    for (index, raw) in contents.split_inclusive(|&b| b == b'\n').enumerate() {
+        let line = raw.strip_suffix(b"\n").unwrap_or(raw);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+
+        match replace_line(config, line, template) {
+            Some(new_line) => {
+                matched += 1;
+                edited.extend_from_slice(&new_line);
+                if new_line != line {
+                    changes.push((index as u64 + 1, line, new_line));
+                }
+            }
+            None => edited.extend_from_slice(line),
+        }
+        edited.extend_from_slice(&raw[line.len()..]);
+    }
+
+    if changes.is_empty() {
+        return Ok(matched);
+    }
+
+    if config.dry_run {
+        write_diff(out, &path.display().to_string(), &changes)?;
+    } else {
+        write_atomically(&fs::canonicalize(path)?, &edited)?;
+    }
+
+    Ok(matched)
+}
+
+/// `line` with every match replaced, or `None` if nothing matches.
+fn replace_line(config: &Config, line: &[u8], template: &str) -> Option<Vec<u8>> {
+    let text = String::from_utf8_lossy(line);
+    let matcher = config.searcher.matcher();
+    let spans = matcher.find_iter(&text);
+    if spans.is_empty() {
+        return None;
+    }
+    let (replaced, replacements) = matcher.replace_all(&text, template);
+
+    let raw = raw_offsets(line);
+    let mut new_line = Vec::with_capacity(replaced.len());
+    let mut last = 0;
+    for (span, replacement) in spans.iter().zip(replacements) {
+        new_line.extend_from_slice(&line[raw[last]..raw[span.start]]);
+        new_line.extend_from_slice(replaced[replacement].as_bytes());
+        last = span.end;
+    }
+    new_line.extend_from_slice(&line[raw[last]..]);
+
+    Some(new_line)
+}
+
+/// For each byte offset in the lossy decoding of `line`, and its end, the
+/// offset in `line` itself. Each run of invalid bytes decodes to one U+FFFD,
+/// all of which maps to the start of the run.
+fn raw_offsets(line: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(line.len() + 1);
+    let mut raw = 0;
+
+    for chunk in line.utf8_chunks() {
+        offsets.extend(raw..raw + chunk.valid().len());
+        raw += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            offsets.extend([raw; char::REPLACEMENT_CHARACTER.len_utf8()]);
+            raw += chunk.invalid().len();
+        }
+    }
+    offsets.push(raw);
+
+    offsets
+}
+
+/// One hunk per changed line. A replacement can contain newlines, which
+/// shifts the line numbers of later hunks in the new file.
+fn write_diff(
+    out: &mut impl Write,
+    name: &str,
+    changes: &[(u64, &[u8], Vec<u8>)],
+) -> io::Result<()> {
+    writeln!(out, "--- {name}")?;
+    writeln!(out, "+++ {name}")?;
+
+    let mut shift: i64 = 0;
+    for (number, old, new) in changes {
+        let new_lines: Vec<&[u8]> = new.split(|&b| b == b'\n').collect();
+        let new_number = *number as i64 + shift;
+
+        if new_lines.len() == 1 {
+            writeln!(out, "@@ -{number} +{new_number} @@")?;
+        } else {
+            writeln!(out, "@@ -{number} +{new_number},{} @@", new_lines.len())?;
+        }
+        out.write_all(b"-")?;
+        out.write_all(old)?;
+        out.write_all(b"\n")?;
+        for line in &new_lines {
+            out.write_all(b"+")?;
+            out.write_all(line)?;
+            out.write_all(b"\n")?;
+        }
+
+        shift += new_lines.len() as i64 - 1;
+    }
+
+    Ok(())
+}
+
+/// Replace the file at `path`, which must not be a symlink, keeping its
+/// permissions and, where there are such things, its owner.
+fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let metadata = fs::metadata(path)?;
+
+    let mut file = NamedTempFile::new_in(dir)?;
+    file.write_all(contents)?;
+    copy_owner(file.as_file(), &metadata)?;
+    file.as_file().set_permissions(metadata.permissions())?;
+    file.persist(path).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+/// Give `file` the owner and group in `metadata`. Only root can give a
+/// file away, so editing someone else's file fails here rather than
+/// quietly taking it over.
+#[cfg(unix)]
+fn copy_owner(file: &File, metadata: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::fs::{fchown, MetadataExt};
+
+    fchown(file, Some(metadata.uid()), Some(metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn copy_owner(_: &File, _: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::args;
+    use tempfile::TempDir;
+
+    fn in_place(path: &Path, replacement: &str, query: &str) -> Config {
+        Config::build(args(&[
+            "minigrep",
+            &format!("--replace={replacement}"),
+            "--in-place",
+            query,
+            path.to_str().unwrap(),
+        ]))
+        .unwrap()
+    }
+
+    /// A file in a directory of its own, removed when the `TempDir` is.
+    fn file(contents: &[u8]) -> (TempDir, std::path::PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file");
+        fs::write(&path, contents).unwrap();
+        (dir, path)
+    }
+
+    #[test]
+    fn edits_files_in_place_keeping_line_endings() {
+        let (_dir, path) = file(b"one\r\ntwo\nthree two\n");
+        let config = in_place(&path, "2", "two");
+
+        let mut out = Vec::new();
+        assert_eq!(2, edit_file(&config, &path, &mut out).unwrap());
+        assert!(out.is_empty());
+        assert_eq!("one\r\n2\nthree 2\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn dry_run_prints_a_diff_and_leaves_the_file_alone() {
+        let (_dir, path) = file(b"one\ntwo\nthree\n");
+        let name = path.to_str().unwrap();
+        let config = Config::build(args(&[
+            "minigrep",
+            "-E",
+            "--replace=a\nb",
+            "--in-place",
+            "--dry-run",
+            "^t",
+            name,
+        ]))
+        .unwrap();
+
+        let mut out = Vec::new();
+        assert_eq!(2, edit_file(&config, &path, &mut out).unwrap());
+        assert_eq!(
+            format!(
+                "--- {name}\n+++ {name}\n\
+                 @@ -2 +2,2 @@\n-two\n+a\n+bwo\n\
+                 @@ -3 +4,2 @@\n-three\n+a\n+bhree\n"
+            ),
+            String::from_utf8(out).unwrap()
+        );
+        assert_eq!("one\ntwo\nthree\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn keeps_bytes_that_are_not_utf8() {
+        let (_dir, path) = file(b"caf\xe9 two\n\xff\xfe\n");
+        let config = in_place(&path, "2", "two");
+
+        assert_eq!(1, edit_file(&config, &path, &mut Vec::new()).unwrap());
+        assert_eq!(b"caf\xe9 2\n\xff\xfe\n".to_vec(), fs::read(&path).unwrap());
+    }
+
+    #[test]
+    fn matches_whole_lines_as_replace_does() {
+        let (_dir, path) = file(b"\xfftwo\ntwo\n\xfe two \xfd\n");
+        let name = path.to_str().unwrap();
+        let config = Config::build(args(&[
+            "minigrep",
+            "--replace=2",
+            "--in-place",
+            "-x",
+            "two",
+            name,
+        ]))
+        .unwrap();
+        assert_eq!(1, edit_file(&config, &path, &mut Vec::new()).unwrap());
+        assert_eq!(
+            b"\xfftwo\n2\n\xfe two \xfd\n".to_vec(),
+            fs::read(&path).unwrap()
+        );
+
+        let config = in_place(&path, "2", "two");
+        assert_eq!(2, edit_file(&config, &path, &mut Vec::new()).unwrap());
+        assert_eq!(
+            b"\xff2\n2\n\xfe 2 \xfd\n".to_vec(),
+            fs::read(&path).unwrap()
+        );
+    }
+
+    #[test]
+    fn counts_lines_that_match_even_if_unchanged() {
+        let (_dir, path) = file(b"one\ntwo\n");
+        let config = in_place(&path, "two", "two");
+
+        assert_eq!(1, edit_file(&config, &path, &mut Vec::new()).unwrap());
+        assert_eq!("one\ntwo\n", fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn edits_the_target_of_a_symlink_and_keeps_its_mode() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let (dir, path) = file(b"one two\n");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        let link = dir.path().join("link");
+        symlink(&path, &link).unwrap();
+        let config = in_place(&link, "2", "two");
+
+        assert_eq!(1, edit_file(&config, &link, &mut Vec::new()).unwrap());
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!("one 2\n", fs::read_to_string(&path).unwrap());
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o640, mode & 0o777);
+    }
+}
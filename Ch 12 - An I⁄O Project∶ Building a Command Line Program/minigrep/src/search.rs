@@ -0,0 +1,202 @@
+use crate::cli::SortBy;
+use crate::printer::Printer;
+use crate::structured;
+use crate::Config;
+use searcher::{Match, Sink};
+use std::io::{self, BufRead, Write};
+use std::ops::Range;
+
+/// A matching line held back to be printed in `--sort` order.
+struct Ranked {
+    distance: u32,
+    number: u64,
+    offset: u64,
+    text: String,
+    spans: Vec<Range<usize>>,
+}
+
+/// Prints what the searcher finds in one input, as `config` asks.
+struct PrinterSink<'a, W> {
+    config: &'a Config,
+    printer: &'a Printer,
+    name: &'a str,
+    out: &'a mut W,
+    ranked: Vec<Ranked>,
+    /// Lines selected so far, for closing the input if the search fails.
+    selected: u64,
+    finished: bool,
+}
+
+impl<'a, W: Write> PrinterSink<'a, W> {
+    fn new(config: &'a Config, printer: &'a Printer, name: &'a str, out: &'a mut W) -> Self {
+        PrinterSink {
+            config,
+            printer,
+            name,
+            out,
+            ranked: Vec::new(),
+            selected: 0,
+            finished: false,
+        }
+    }
+
+    /// Whether lines are printed at all, rather than just counted.
+    fn prints_lines(&self) -> bool {
+        !self.config.count && !self.lists_files() && !self.config.quiet
+    }
+
+    fn lists_files(&self) -> bool {
+        self.config.files_with_matches || self.config.files_without_match
+    }
+}
+
+impl<W: Write> Sink for PrinterSink<'_, W> {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        self.selected += 1;
+
+        // One match is all it takes to decide.
+        if self.lists_files() || self.config.quiet {
+            return Ok(false);
+        }
+        if self.config.count {
+            return Ok(true);
+        }
+
+        let matcher = self.config.searcher.matcher();
+        let distance = match self.config.sort {
+            Some(SortBy::Score) => matcher.distance(m.line).unwrap_or(0),
+            None => 0,
+        };
+        let (text, spans) = match &self.config.replace {
+            Some(template) => matcher.replace_all(m.line, template),
+            None => (m.line.to_string(), m.spans.clone()),
+        };
+
+        if self.config.sort.is_some() {
+            self.ranked.push(Ranked {
+                distance,
+                number: m.line_number,
+                offset: m.byte_offset,
+                text,
+                spans,
+            });
+            return Ok(true);
+        }
+
+        let m = Match {
+            line: &text,
+            spans,
+            ..*m
+        };
+        self.printer.matched(self.out, self.name, &m)?;
+        Ok(true)
+    }
+
+    fn context(&mut self, line_number: u64, byte_offset: u64, line: &str) -> io::Result<bool> {
+        if self.prints_lines() {
+            self.printer
+                .context(self.out, self.name, line_number, byte_offset, line)?;
+        }
+        Ok(true)
+    }
+
+    fn context_break(&mut self) -> io::Result<bool> {
+        if self.prints_lines() {
+            self.printer.separator(self.out)?;
+        }
+        Ok(true)
+    }
+
+    fn binary_match(&mut self, _: &Match) -> io::Result<bool> {
+        self.selected += 1;
+        if self.config.count && !self.config.quiet {
+            return Ok(true);
+        }
+        if self.prints_lines() {
+            // Printing lines of a binary file would only garble the
+            // terminal, so say that it matched and move on, like grep.
+            self.printer.binary_match(self.out, self.name)?;
+        }
+        Ok(false)
+    }
+
+    fn finish(&mut self, matches: u64, binary: bool) -> io::Result<()> {
+        self.finished = true;
+
+        // A stable sort keeps equally close lines in file order.
+        self.ranked.sort_by_key(|line| line.distance);
+        for line in self.ranked.drain(..) {
+            let m = Match {
+                line_number: line.number,
+                byte_offset: line.offset,
+                line: &line.text,
+                spans: line.spans,
+            };
+            self.printer.matched(self.out, self.name, &m)?;
+        }
+
+        if self.config.quiet {
+            // Nothing is printed; the exit status says it all.
+        } else if self.lists_files() {
+            if (matches > 0) == self.config.files_with_matches {
+                self.printer.file_name(self.out, self.name)?;
+            }
+        } else if self.config.count {
+            self.printer.count(self.out, self.name, matches)?;
+        }
+
+        self.printer.end(self.out, self.name, matches, binary)
+    }
+}
+
+/// Search `reader` line by line, printing results for the input `name`
+/// as `config` asks, and return the number of lines selected.
+pub fn search_reader(
+    config: &Config,
+    printer: &Printer,
+    name: &str,
+    reader: impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<u64> {
+    search_with(config, printer, name, out, |sink| match &config.field {
+        Some(field) => structured::search(field, &config.searcher, reader, sink),
+        None => config.searcher.search_reader(reader, sink),
+    })
+}
+
+/// Like `search_reader`, for input that is all in memory, such as a
+/// memory-mapped file.
+pub fn search_slice(
+    config: &Config,
+    printer: &Printer,
+    name: &str,
+    haystack: &[u8],
+    out: &mut impl Write,
+) -> io::Result<u64> {
+    search_with(config, printer, name, out, |sink| match &config.field {
+        Some(field) => structured::search(field, &config.searcher, haystack, sink),
+        None => config.searcher.search_slice(haystack, sink),
+    })
+}
+
+/// Run `search` with a sink printing for the input `name`. The searcher
+/// only finishes the sink when the search succeeds, so if it fails the
+/// input is closed here, and `--json` output always has an `end` for each
+/// `begin`.
+fn search_with<W: Write>(
+    config: &Config,
+    printer: &Printer,
+    name: &str,
+    out: &mut W,
+    search: impl FnOnce(&mut PrinterSink<W>) -> io::Result<u64>,
+) -> io::Result<u64> {
+    printer.begin(out, name)?;
+    let mut sink = PrinterSink::new(config, printer, name, out);
+
+    let result = search(&mut sink);
+    if result.is_err() && !sink.finished {
+        // The search error is the one worth reporting.
+        let _ = printer.end(sink.out, name, sink.selected, false);
+    }
+    result
+}
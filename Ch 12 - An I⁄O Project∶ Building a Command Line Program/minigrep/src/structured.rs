@@ -0,0 +1,354 @@
+//! `--csv-column` and `--json-path`: matching one field of each record
+//! instead of whole lines.
+
+use searcher::lines::LineReader;
+use searcher::{Match, Searcher, Sink};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::io::{self, BufRead, Read};
+use std::rc::Rc;
+
+/// Which part of each record to match against.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Field {
+    /// The named column of CSV input, which must start with a header row.
+    CsvColumn(String),
+    /// The value at a path into each line of JSON Lines input.
+    JsonPath(JsonPath),
+}
+
+/// A path to a value inside a JSON document, such as `.user.name` or
+/// `$.items[0].id`. Keys are separated by dots and array elements are
+/// picked with `[N]`; a leading `$` or `.` is optional.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath(Vec<Step>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    Index(usize),
+}
+
+impl JsonPath {
+    pub fn parse(expr: &str) -> Result<JsonPath, String> {
+        let invalid = |why: &str| format!("invalid JSON path '{expr}': {why}");
+
+        let rest = expr.strip_prefix('$').unwrap_or(expr);
+        let rest = rest.strip_prefix('.').unwrap_or(rest);
+        let mut steps = Vec::new();
+
+        for part in rest.split('.') {
+            let (key, mut indexes) = part.split_at(part.find('[').unwrap_or(part.len()));
+
+            if key.is_empty() && indexes.is_empty() {
+                return Err(invalid("empty key"));
+            }
+            if !key.is_empty() {
+                steps.push(Step::Key(key.to_string()));
+            }
+
+            while let Some(rest) = indexes.strip_prefix('[') {
+                let (index, rest) = rest
+                    .split_once(']')
+                    .ok_or_else(|| invalid("unclosed '['"))?;
+                let index = index
+                    .parse()
+                    .map_err(|_| invalid("array index must be a number"))?;
+                steps.push(Step::Index(index));
+                indexes = rest;
+            }
+            if !indexes.is_empty() {
+                return Err(invalid("unexpected text after ']'"));
+            }
+        }
+
+        Ok(JsonPath(steps))
+    }
+
+    /// The value at this path in `value`, if there is one.
+    pub fn select<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        self.0.iter().try_fold(value, |value, step| match step {
+            Step::Key(key) => value.get(key),
+            Step::Index(index) => value.get(index),
+        })
+    }
+}
+
+/// The text of a JSON value to match against: strings as they are, without
+/// quotes or escapes, and anything else as JSON.
+fn value_text(value: &Value) -> Cow<'_, str> {
+    match value {
+        Value::String(s) => Cow::Borrowed(s),
+        other => Cow::Owned(other.to_string()),
+    }
+}
+
+/// Search the records in `reader`, matching `searcher`'s matcher against
+/// `field` of each, and report whole records to `sink` as they appear in
+/// the input. Records without the field never match, so `-v` selects them.
+/// Context is not reported, and there are no spans to highlight.
+pub fn search(
+    field: &Field,
+    searcher: &Searcher,
+    reader: impl BufRead,
+    sink: &mut impl Sink,
+) -> io::Result<u64> {
+    let mut records = Records {
+        searcher,
+        sink,
+        count: 0,
+    };
+
+    match field {
+        Field::CsvColumn(name) => records.csv(name, reader)?,
+        Field::JsonPath(path) => records.json(path, reader)?,
+    }
+
+    let count = records.count;
+    sink.finish(count, false)?;
+    Ok(count)
+}
+
+struct Records<'a, S> {
+    searcher: &'a Searcher,
+    sink: &'a mut S,
+    count: u64,
+}
+
+impl<S: Sink> Records<'_, S> {
+    /// Select the record if its field matches, returning false to stop.
+    fn check(
+        &mut self,
+        field: Option<&str>,
+        line_number: u64,
+        byte_offset: u64,
+        record: &str,
+    ) -> io::Result<bool> {
+        let matched = field.is_some_and(|field| self.searcher.matcher().is_match(field));
+        if matched == self.searcher.invert_match() {
+            return Ok(true);
+        }
+        self.count += 1;
+
+        self.sink.matched(&Match {
+            line_number,
+            byte_offset,
+            line: record,
+            spans: Vec::new(),
+        })
+    }
+
+    fn json(&mut self, path: &JsonPath, reader: impl BufRead) -> io::Result<()> {
+        let mut lines = LineReader::new(reader);
+
+        while let Some(line) = lines.next_line()? {
+            // Lines that are not JSON have no fields to match.
+            let value = serde_json::from_str::<Value>(&line.text).ok();
+            let field = value.as_ref().and_then(|value| path.select(value));
+            let field = field.map(value_text);
+
+            if !self.check(field.as_deref(), line.number, line.offset, &line.text)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// CSV records can span lines, so the parser finds where each ends,
+    /// and its original text is taken from a copy of the input kept since
+    /// the end of the last one.
+    fn csv(&mut self, name: &str, reader: impl BufRead) -> io::Result<()> {
+        let kept = Rc::new(RefCell::new(Vec::new()));
+        let mut csv = csv::ReaderBuilder::new()
+            .flexible(true)
+            .from_reader(Recorder {
+                inner: reader,
+                kept: Rc::clone(&kept),
+            });
+        let column = csv
+            .byte_headers()?
+            .iter()
+            .position(|header| header == name.as_bytes())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("no column named '{name}' in the CSV header"),
+                )
+            })?;
+
+        // `kept` holds the input from byte `base` on.
+        let mut base = 0;
+        let mut record = csv::ByteRecord::new();
+        let (mut start, mut line_number) = (csv.position().byte(), csv.position().line());
+        while csv.read_byte_record(&mut record)? {
+            let end = csv.position().byte();
+            let mut kept = kept.borrow_mut();
+            kept.drain(..(start - base) as usize);
+            base = start;
+
+            // The reader skips blank lines before a record; so must we.
+            for &b in kept.iter().take_while(|&&b| b == b'\n' || b == b'\r') {
+                line_number += u64::from(b == b'\n');
+                start += 1;
+            }
+
+            let raw = &kept[(start - base) as usize..(end - base) as usize];
+            let raw = raw.strip_suffix(b"\n").unwrap_or(raw);
+            let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+            let text = String::from_utf8_lossy(raw);
+            let field = record.get(column).map(String::from_utf8_lossy);
+
+            if !self.check(field.as_deref(), line_number, start, &text)? {
+                break;
+            }
+            (start, line_number) = (end, csv.position().line());
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads from `inner`, keeping a copy of what it reads in `kept`.
+struct Recorder<R> {
+    inner: R,
+    kept: Rc<RefCell<Vec<u8>>>,
+}
+
+impl<R: Read> Read for Recorder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.kept.borrow_mut().extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parses_and_follows_json_paths() {
+        let value = json!({"user": {"name": "ann", "roles": ["admin", "dev"]}, "n": 3});
+        let select = |expr| JsonPath::parse(expr).unwrap().select(&value).cloned();
+
+        assert_eq!(Some(json!("ann")), select(".user.name"));
+        assert_eq!(Some(json!("dev")), select("$.user.roles[1]"));
+        assert_eq!(Some(json!(3)), select("n"));
+        assert_eq!(None, select("user.roles[2]"));
+        assert_eq!(None, select("user.name.first"));
+
+        for bad in ["", "user..name", "roles[x]", "roles[1", "roles[1]x"] {
+            assert!(JsonPath::parse(bad).is_err(), "{bad:?}");
+        }
+    }
+
+    fn search_records(field: Field, builder: searcher::SearcherBuilder, input: &str) -> String {
+        let mut out = String::new();
+        let searcher = builder.build().unwrap();
+        let mut sink = Lines(&mut out);
+        search(&field, &searcher, input.as_bytes(), &mut sink).unwrap();
+        out
+    }
+
+    #[test]
+    fn streams_csv_records() {
+        let input = format!("name\nbob\n{}", "x\n".repeat(1 << 20));
+        let searcher = Searcher::builder().pattern("bob").build().unwrap();
+        let read = Rc::new(RefCell::new(Vec::new()));
+        let reader = io::BufReader::new(Recorder {
+            inner: input.as_bytes(),
+            kept: Rc::clone(&read),
+        });
+
+        let mut first = None;
+        search(
+            &Field::CsvColumn("name".to_string()),
+            &searcher,
+            reader,
+            &mut First(&mut first),
+        )
+        .unwrap();
+        assert_eq!(Some(2), first);
+        assert!(read.borrow().len() < input.len() / 10);
+    }
+
+    /// Stops at the first selected record.
+    struct First<'a>(&'a mut Option<u64>);
+
+    impl Sink for First<'_> {
+        fn matched(&mut self, m: &Match) -> io::Result<bool> {
+            *self.0 = Some(m.line_number);
+            Ok(false)
+        }
+    }
+
+    /// Records each selected record with its line number and offset.
+    struct Lines<'a>(&'a mut String);
+
+    impl Sink for Lines<'_> {
+        fn matched(&mut self, m: &Match) -> io::Result<bool> {
+            *self.0 += &format!("{}@{}:{}\n", m.line_number, m.byte_offset, m.line);
+            Ok(true)
+        }
+    }
+
+    #[test]
+    fn matches_one_csv_column_and_prints_whole_records() {
+        let input = "name,note\r\nann,\"likes\nbob\"\r\n\nbob,hi\n";
+        let column = || Field::CsvColumn("name".to_string());
+
+        assert_eq!(
+            "5@29:bob,hi\n",
+            search_records(column(), Searcher::builder().pattern("bob"), input)
+        );
+        assert_eq!(
+            "2@11:ann,\"likes\nbob\"\n",
+            search_records(
+                column(),
+                Searcher::builder().pattern("bob").invert_match(true),
+                input
+            )
+        );
+
+        let searcher = Searcher::builder().pattern("x").build().unwrap();
+        let err = search(
+            &Field::CsvColumn("missing".to_string()),
+            &searcher,
+            input.as_bytes(),
+            &mut Vec::new(),
+        )
+        .unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+
+    #[test]
+    fn matches_a_json_field_of_each_line() {
+        let input = r#"{"level":"error","msg":"disk full"}
+{"level":"info","msg":"error count is 0"}
+not json
+{"level":"error","msg":"timeout"}
+"#;
+        let path = || Field::JsonPath(JsonPath::parse(".level").unwrap());
+
+        assert_eq!(
+            "1@0:{\"level\":\"error\",\"msg\":\"disk full\"}\n\
+             4@87:{\"level\":\"error\",\"msg\":\"timeout\"}\n",
+            search_records(
+                path(),
+                Searcher::builder().pattern("error").line(true),
+                input
+            )
+        );
+        assert_eq!(
+            "3@78:not json\n",
+            search_records(
+                Field::JsonPath(JsonPath::parse("msg").unwrap()),
+                Searcher::builder().pattern("").invert_match(true),
+                input
+            )
+        );
+    }
+}
@@ -0,0 +1,31 @@
+//! Helpers shared by the unit tests of several modules.
+
+use std::fs::{self, File};
+use std::time::{Duration, SystemTime};
+use tempfile::TempDir;
+
+/// A command line, program name first, as `Config::build` takes it.
+pub fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|s| s.to_string()).collect()
+}
+
+/// A temporary directory holding `files`, given as paths relative to it and
+/// their contents. Every file was last modified an hour ago, so that none
+/// looks as if it changed just now; the index distrusts files that do.
+pub fn tree(files: &[(&str, &str)]) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+
+    for (path, contents) in files {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, contents).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .and_then(|file| file.set_modified(an_hour_ago))
+            .unwrap();
+    }
+
+    dir
+}
@@ -0,0 +1,176 @@
+use crate::index::INDEX_FILE;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::path::{Path, PathBuf};
+
+/// Expands the paths given on the command line into the files to search.
+///
+/// Files named directly are always searched. Directories are walked
+/// recursively, skipping hidden entries and anything matched by `.gitignore`,
+/// `.ignore` or `.git/info/exclude`, and their files are filtered by the
+/// `--include` and `--exclude` globs, which match against the file name.
+pub struct Walker {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    hidden: bool,
+    no_ignore: bool,
+}
+
+impl Walker {
+    pub fn new(
+        include: &[String],
+        exclude: &[String],
+        hidden: bool,
+        no_ignore: bool,
+    ) -> Result<Walker, String> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(glob_set(include)?)
+        };
+
+        Ok(Walker {
+            include,
+            exclude: glob_set(exclude)?,
+            hidden,
+            no_ignore,
+        })
+    }
+
+    /// Yield every file to search, or a message for each path that could not
+    /// be walked.
+    pub fn files<'a>(
+        &'a self,
+        paths: &'a [PathBuf],
+    ) -> impl Iterator<Item = Result<PathBuf, String>> + Send + 'a {
+        paths
+            .iter()
+            .flat_map(move |path| -> Box<dyn Iterator<Item = _> + Send> {
+                if path.is_dir() {
+                    Box::new(self.walk(path))
+                } else {
+                    Box::new(std::iter::once(Ok(path.clone())))
+                }
+            })
+    }
+
+    fn walk<'a>(&'a self, dir: &Path) -> impl Iterator<Item = Result<PathBuf, String>> + Send + 'a {
+        WalkBuilder::new(dir)
+            .hidden(!self.hidden)
+            .ignore(!self.no_ignore)
+            .git_ignore(!self.no_ignore)
+            .git_global(!self.no_ignore)
+            .git_exclude(!self.no_ignore)
+            .parents(!self.no_ignore)
+            .require_git(false)
+            .sort_by_file_name(|a, b| a.cmp(b))
+            .build()
+            .filter_map(move |entry| match entry {
+                Ok(entry) if entry.file_type().is_some_and(|t| t.is_file()) => {
+                    let path = entry.into_path();
+                    self.wanted(&path).then_some(Ok(path))
+                }
+                Ok(_) => None,
+                Err(e) => Some(Err(e.to_string())),
+            })
+    }
+
+    fn wanted(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name() else {
+            return false;
+        };
+        // Not even --hidden makes the index worth searching.
+        if name == INDEX_FILE {
+            return false;
+        }
+
+        let included = self.include.as_ref().is_none_or(|set| set.is_match(name));
+        included && !self.exclude.is_match(name)
+    }
+}
+
+fn glob_set(globs: &[String]) -> Result<GlobSet, String> {
+    let mut builder = GlobSetBuilder::new();
+
+    for glob in globs {
+        builder.add(Glob::new(glob).map_err(|e| format!("invalid glob '{glob}': {e}"))?);
+    }
+
+    builder.build().map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::tree;
+
+    const FILES: &[(&str, &str)] = &[
+        ("a.txt", "a"),
+        ("b.rs", "b"),
+        ("sub/c.txt", "c"),
+        ("sub/d.log", "d"),
+        ("target/e.txt", "e"),
+        (".hidden/f.txt", "f"),
+        (".gitignore", "target/\n*.log\n"),
+    ];
+
+    fn names(walker: &Walker, root: &Path) -> Vec<String> {
+        walker
+            .files(&[root.to_path_buf()])
+            .map(|path| {
+                let path = path.unwrap();
+                let relative = path.strip_prefix(root).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn honours_ignore_files_and_skips_hidden() {
+        let root = tree(FILES);
+        let walker = Walker::new(&[], &[], false, false).unwrap();
+
+        assert_eq!(
+            vec!["a.txt", "b.rs", "sub/c.txt"],
+            names(&walker, root.path())
+        );
+    }
+
+    #[test]
+    fn no_ignore_and_hidden_search_everything() {
+        let root = tree(FILES);
+        let walker = Walker::new(&[], &[], true, true).unwrap();
+
+        assert_eq!(
+            vec![
+                ".gitignore",
+                ".hidden/f.txt",
+                "a.txt",
+                "b.rs",
+                "sub/c.txt",
+                "sub/d.log",
+                "target/e.txt"
+            ],
+            names(&walker, root.path())
+        );
+    }
+
+    #[test]
+    fn include_and_exclude_globs_filter_file_names() {
+        let root = tree(FILES);
+        let walker =
+            Walker::new(&["*.txt".to_string()], &["c.*".to_string()], false, false).unwrap();
+
+        assert_eq!(vec!["a.txt"], names(&walker, root.path()));
+    }
+
+    #[test]
+    fn named_files_are_always_searched() {
+        let root = tree(FILES);
+        let walker = Walker::new(&[], &["*.log".to_string()], false, false).unwrap();
+        let log = root.path().join("sub/d.log");
+
+        let files: Vec<_> = walker.files(std::slice::from_ref(&log)).collect();
+        assert_eq!(vec![Ok(log)], files);
+    }
+}
@@ -0,0 +1,79 @@
+//! Compares the memchr-based literal search with the line-by-line search
+//! that `minigrep_iter` used to do.
+//!
+//! Run with `cargo bench -p searcher`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use searcher::{Match, Searcher, Sink};
+use std::io;
+
+/// About 4 MiB of log-like text in which one line in a thousand matches.
+fn corpus() -> String {
+    let mut corpus = String::new();
+    for i in 0..60_000 {
+        if i % 1000 == 0 {
+            corpus += "2024-05-01 12:00:00 ERROR payment declined: card expired\n";
+        } else {
+            corpus += "2024-05-01 12:00:00 INFO request served in 12ms by worker 7\n";
+        }
+    }
+    corpus
+}
+
+/// The implementation `minigrep_iter::search` started out with.
+fn search_lines<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| line.contains(query))
+        .collect()
+}
+
+/// Counts matching lines without keeping them.
+struct Count(u64);
+
+impl Sink for Count {
+    fn matched(&mut self, _: &Match) -> io::Result<bool> {
+        self.0 += 1;
+        Ok(true)
+    }
+}
+
+fn bench_search(c: &mut Criterion) {
+    let corpus = corpus();
+    let mut group = c.benchmark_group("literal");
+    group.throughput(Throughput::Bytes(corpus.len() as u64));
+
+    for query in ["card expired", "worker 7"] {
+        group.bench_with_input(BenchmarkId::new("lines_filter", query), query, |b, q| {
+            b.iter(|| search_lines(black_box(q), &corpus).len())
+        });
+        group.bench_with_input(BenchmarkId::new("memmem", query), query, |b, q| {
+            b.iter(|| searcher::search(black_box(q), &corpus).len())
+        });
+
+        let searcher = Searcher::builder().pattern(query).build().unwrap();
+        group.bench_with_input(BenchmarkId::new("search_reader", query), query, |b, _| {
+            b.iter(|| {
+                let mut count = Count(0);
+                searcher
+                    .search_reader(corpus.as_bytes(), &mut count)
+                    .unwrap();
+                count.0
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("search_slice", query), query, |b, _| {
+            b.iter(|| {
+                let mut count = Count(0);
+                searcher
+                    .search_slice(corpus.as_bytes(), &mut count)
+                    .unwrap();
+                count.0
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_search);
+criterion_main!(benches);
@@ -0,0 +1,267 @@
+use crate::matcher::{self, Matcher};
+use std::collections::HashMap;
+use std::iter;
+use std::ops::Range;
+
+/// Matches lines containing the query with at most `max_edits` insertions,
+/// deletions or substitutions (Levenshtein distance), for `--fuzzy`.
+///
+/// Lines are scanned with Myers' bit-vector algorithm, which keeps the
+/// whole column of the edit-distance table in one `u64` and so handles a
+/// character in a few word operations. Queries longer than 64 characters
+/// fall back to the plain dynamic-programming table. Ignoring case compares
+/// lines and query after Unicode full case folding, as literal matching
+/// does, so an edit is an edit to the folded text.
+pub struct FuzzyMatcher {
+    query: Vec<char>,
+    max_edits: u32,
+    ignore_case: bool,
+    /// For each query character, the bit positions where it occurs.
+    peq: HashMap<char, u64>,
+}
+
+impl FuzzyMatcher {
+    pub fn new(query: &str, max_edits: u32, ignore_case: bool) -> FuzzyMatcher {
+        let query: Vec<char> = query
+            .char_indices()
+            .flat_map(|(i, c)| fold(i, c, ignore_case))
+            .map(|(_, c)| c)
+            .collect();
+
+        let mut peq = HashMap::new();
+        if query.len() <= 64 {
+            for (i, &c) in query.iter().enumerate() {
+                *peq.entry(c).or_insert(0) |= 1 << i;
+            }
+        }
+
+        FuzzyMatcher {
+            query,
+            max_edits,
+            ignore_case,
+            peq,
+        }
+    }
+
+    /// The smallest edit distance between the query and any substring of
+    /// `line`.
+    fn best_distance(&self, line: &str) -> u32 {
+        let m = self.query.len();
+        if m == 0 {
+            return 0;
+        }
+        if m > 64 {
+            return self
+                .table(line)
+                .iter()
+                .map(|&(cost, _)| cost)
+                .min()
+                .unwrap_or(m as u32);
+        }
+
+        let mask = if m == 64 { !0 } else { (1u64 << m) - 1 };
+        let high = 1u64 << (m - 1);
+        let mut pv = mask;
+        let mut mv = 0u64;
+        let mut score = m as u32;
+        let mut best = score;
+
+        for (_, c) in self.chars(line) {
+            let eq = self.peq.get(&c).copied().unwrap_or(0);
+            let xv = eq | mv;
+            let xh = (((eq & pv).wrapping_add(pv)) ^ pv) | eq;
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+
+            if ph & high != 0 {
+                score += 1;
+            } else if mh & high != 0 {
+                score -= 1;
+            }
+
+            // A match may start anywhere in the line, so the top row of the
+            // table stays at zero and nothing is shifted in.
+            ph <<= 1;
+            mh <<= 1;
+            pv = (mh | !(xv | ph)) & mask;
+            mv = ph & xv & mask;
+
+            best = best.min(score);
+            if best == 0 {
+                break;
+            }
+        }
+
+        best
+    }
+
+    /// The characters of `line` to compare with the query, each with the
+    /// byte range of the character of `line` it came from.
+    fn chars<'l>(&self, line: &'l str) -> impl Iterator<Item = (Range<usize>, char)> + 'l {
+        let ignore_case = self.ignore_case;
+        line.char_indices()
+            .flat_map(move |(i, c)| fold(i, c, ignore_case))
+    }
+
+    /// The last row of the edit-distance table for `line`: for each
+    /// character position, the cost of the best match ending just after it
+    /// and the character position that match starts at. Entry 0 is the
+    /// empty prefix. Positions count the characters of `chars`.
+    fn table(&self, line: &str) -> Vec<(u32, usize)> {
+        let text: Vec<char> = self.chars(line).map(|(_, c)| c).collect();
+
+        // Row 0: the empty query matches at every position for free.
+        let mut row: Vec<(u32, usize)> = (0..=text.len()).map(|j| (0, j)).collect();
+
+        for (i, &q) in self.query.iter().enumerate() {
+            let mut next = Vec::with_capacity(row.len());
+            next.push((i as u32 + 1, 0));
+
+            for (j, &t) in text.iter().enumerate() {
+                let (diagonal, start) = row[j];
+                let substitute = (diagonal + u32::from(q != t), start);
+                let skip_query = (row[j + 1].0 + 1, row[j + 1].1);
+                let skip_text = (next[j].0 + 1, next[j].1);
+
+                let best = [skip_query, skip_text]
+                    .into_iter()
+                    .fold(substitute, |a, b| if b.0 < a.0 { b } else { a });
+                next.push(best);
+            }
+
+            row = next;
+        }
+
+        row
+    }
+}
+
+/// `c`, or its case folding, each character paired with the byte range of
+/// `c` at `i`.
+fn fold(i: usize, c: char, ignore_case: bool) -> impl Iterator<Item = (Range<usize>, char)> {
+    let (same, folded) = if ignore_case {
+        (None, Some(matcher::fold(c)))
+    } else {
+        (Some(iter::once(c)), None)
+    };
+    let span = i..i + c.len_utf8();
+
+    same.into_iter()
+        .flatten()
+        .chain(folded.into_iter().flatten())
+        .map(move |f| (span.clone(), f))
+}
+
+impl Matcher for FuzzyMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        let rest = &line[start..];
+        if self.best_distance(rest) > self.max_edits {
+            return None;
+        }
+
+        // Only lines that match get here, so the full table is affordable
+        // for working out where the match is.
+        let row = self.table(rest);
+        let mut end = row.iter().position(|&(cost, _)| cost <= self.max_edits)?;
+        // Keep going while the match gets no worse, so that "colour" is
+        // reported whole rather than as the "colo" that is already close
+        // enough to "color".
+        while end + 1 < row.len() && row[end + 1].0 <= row[end].0 {
+            end += 1;
+        }
+
+        // Widen the match to whole characters of the line where it starts
+        // or ends partway through the folding of one.
+        let spans: Vec<Range<usize>> = self.chars(rest).map(|(span, _)| span).collect();
+        let first = spans.get(row[end].1).map_or(rest.len(), |span| span.start);
+        let last = match end {
+            0 => 0,
+            end => spans[end - 1].end,
+        }
+        .max(first);
+
+        Some(start + first..start + last)
+    }
+
+    fn distance(&self, line: &str) -> Option<u32> {
+        let distance = self.best_distance(line);
+        (distance <= self.max_edits).then_some(distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_within_the_edit_distance() {
+        let matcher = FuzzyMatcher::new("receive", 1, false);
+
+        assert_eq!(Some(0), matcher.distance("we receive it"));
+        assert_eq!(Some(1), matcher.distance("we recive it"));
+        assert_eq!(Some(1), matcher.distance("we receve it"));
+        // Swapping two letters takes two edits.
+        assert_eq!(None, matcher.distance("we recieve it"));
+        assert_eq!(None, matcher.distance("nothing here"));
+    }
+
+    #[test]
+    fn reports_the_span_of_the_match() {
+        let matcher = FuzzyMatcher::new("color", 1, false);
+
+        assert_eq!(vec![4..10], matcher.find_iter("the colour of"));
+        assert_eq!(vec![0..5, 6..10], matcher.find_iter("colir colr"));
+    }
+
+    #[test]
+    fn bit_vector_agrees_with_the_table() {
+        let lines = [
+            "",
+            "abc",
+            "xabcx",
+            "axc",
+            "ba",
+            "aabbcc",
+            "the quick brown fox",
+        ];
+
+        for query in ["abc", "a", "quack", "cab", "brwn fx"] {
+            let matcher = FuzzyMatcher::new(query, 2, false);
+            for line in lines {
+                let table = matcher.table(line).iter().map(|&(cost, _)| cost).min();
+                assert_eq!(
+                    table,
+                    Some(matcher.best_distance(line)),
+                    "{query} in {line}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn long_queries_use_the_table() {
+        let query = "a".repeat(70);
+        let matcher = FuzzyMatcher::new(&query, 2, false);
+
+        assert_eq!(Some(1), matcher.distance(&"a".repeat(69)));
+        assert_eq!(None, matcher.distance(&"a".repeat(67)));
+    }
+
+    #[test]
+    fn ignores_case_when_asked() {
+        assert_eq!(
+            Some(1),
+            FuzzyMatcher::new("Rust", 1, true).distance("TRUTS")
+        );
+        assert_eq!(None, FuzzyMatcher::new("Rust", 1, false).distance("RUST"));
+    }
+
+    #[test]
+    fn folds_case_like_literal_matching() {
+        let matcher = FuzzyMatcher::new("STRASSE", 1, true);
+
+        assert_eq!(Some(0), matcher.distance("die straße"));
+        assert_eq!(Some(1), matcher.distance("die strase"));
+        assert_eq!(vec![4..11], matcher.find_iter("die straße"));
+    }
+}
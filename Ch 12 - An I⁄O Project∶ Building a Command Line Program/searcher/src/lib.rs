@@ -0,0 +1,153 @@
+//! The search engine behind `minigrep` and `minigrep_iter`.
+//!
+//! A [`Searcher`] finds the lines of some input that match its patterns,
+//! along with any context around them, and hands them to a [`Sink`], which
+//! decides what to do with them. The searcher itself never prints anything.
+
+pub mod fuzzy;
+pub mod lines;
+pub mod matcher;
+mod searcher;
+mod sink;
+
+pub use matcher::{MatchOptions, Matcher, Mode};
+pub use searcher::{CaseMode, Searcher, SearcherBuilder};
+pub use sink::{Match, Sink};
+
+use matcher::LiteralMatcher;
+use memchr::memmem;
+
+/// Find every line of `contents` that `matcher` matches.
+pub fn search_matches<'a>(matcher: &dyn Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    contents
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| {
+            let spans = matcher.find_iter(line);
+            if spans.is_empty() {
+                return None;
+            }
+
+            Some(Match {
+                line_number: index as u64 + 1,
+                byte_offset: (line.as_ptr() as usize - contents.as_ptr() as usize) as u64,
+                line,
+                spans,
+            })
+        })
+        .collect()
+}
+
+/// Find the lines of `contents` that contain `query`.
+///
+/// Rather than checking every line, this looks for `query` in the whole of
+/// `contents` with a vectorised substring search and only works out where
+/// the line around each occurrence starts and ends.
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    if query.is_empty() {
+        return contents.lines().collect();
+    }
+
+    let finder = memmem::Finder::new(query);
+    let bytes = contents.as_bytes();
+    let mut results = Vec::new();
+    let mut at = 0;
+
+    while let Some(found) = bytes.get(at..).and_then(|rest| finder.find(rest)) {
+        let bounds = lines::line_bounds(bytes, at + found);
+        at = bounds.end + 1;
+
+        let line = &contents[bounds];
+        // `lines` drops a `\r` before the `\n`, and so must we.
+        let line = match line.strip_suffix('\r') {
+            Some(stripped) if at <= bytes.len() => stripped,
+            _ => line,
+        };
+        if line.contains(query) {
+            results.push(line);
+        }
+    }
+
+    results
+}
+
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    lines_matching(&LiteralMatcher::new(query, true), contents)
+}
+
+fn lines_matching<'a>(matcher: &dyn Matcher, contents: &'a str) -> Vec<&'a str> {
+    search_matches(matcher, contents)
+        .into_iter()
+        .map(|m| m.line)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[allow(clippy::single_range_in_vec_init)]
+    fn records_line_numbers_offsets_and_spans() {
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        assert_eq!(
+            vec![
+                Match {
+                    line_number: 1,
+                    byte_offset: 0,
+                    line: "Rust:",
+                    spans: vec![0..4],
+                },
+                Match {
+                    line_number: 4,
+                    byte_offset: 42,
+                    line: "Trust me.",
+                    spans: vec![1..5],
+                },
+            ],
+            search_matches(&LiteralMatcher::new("rUsT", true), contents)
+        );
+    }
+
+    #[test]
+    fn case_sensitive() {
+        let query = "duct";
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Duct tape.";
+
+        assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+    }
+
+    #[test]
+    fn search_agrees_with_filtering_lines() {
+        let contents = "duct\r\nproductive\nno\n\nduct duct\rduct\r";
+
+        for query in ["duct", "", "\r", "t\nno", "no"] {
+            let expected: Vec<&str> = contents.lines().filter(|l| l.contains(query)).collect();
+            assert_eq!(expected, search(query, contents), "{query:?}");
+        }
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let query = "rUsT";
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        assert_eq!(
+            vec!["Rust:", "Trust me."],
+            search_case_insensitive(query, contents)
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::borrow::Cow;
+use std::io::{self, BufRead};
+use std::ops::Range;
+
+/// How much of the start of the input is checked for NUL bytes to decide
+/// whether it looks binary: the size of a default `BufReader` buffer.
+pub const BINARY_PEEK: usize = 8 * 1024;
+
+/// One line of input, without its line terminator.
+pub struct Line<'a> {
+    /// 1-based line number.
+    pub number: u64,
+    /// Byte offset of the start of the line in the input.
+    pub offset: u64,
+    /// The line's text. Invalid UTF-8 is replaced with U+FFFD.
+    pub text: Cow<'a, str>,
+    /// Whether the raw line contained a NUL byte.
+    pub has_nul: bool,
+}
+
+/// Reads input one line at a time, so that files never have to fit in
+/// memory and need not be valid UTF-8.
+pub struct LineReader<R> {
+    reader: R,
+    buffer: Vec<u8>,
+    number: u64,
+    offset: u64,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> LineReader<R> {
+        LineReader {
+            reader,
+            buffer: Vec::new(),
+            number: 0,
+            offset: 0,
+        }
+    }
+
+    /// Whether the input looks binary, judging by a NUL byte in the first
+    /// `BINARY_PEEK` bytes that have been buffered but not yet read. This
+    /// is the same heuristic grep uses.
+    pub fn looks_binary(&mut self) -> io::Result<bool> {
+        let buffer = self.reader.fill_buf()?;
+        Ok(memchr::memchr(0, &buffer[..buffer.len().min(BINARY_PEEK)]).is_some())
+    }
+
+    pub fn next_line(&mut self) -> io::Result<Option<Line<'_>>> {
+        self.buffer.clear();
+        let read = self.reader.read_until(b'\n', &mut self.buffer)?;
+        if read == 0 {
+            return Ok(None);
+        }
+
+        self.number += 1;
+        let offset = self.offset;
+        self.offset += read as u64;
+
+        let mut end = self.buffer.len();
+        if self.buffer[..end].ends_with(b"\n") {
+            end -= 1;
+        }
+        if self.buffer[..end].ends_with(b"\r") {
+            end -= 1;
+        }
+        let bytes = &self.buffer[..end];
+
+        Ok(Some(Line {
+            number: self.number,
+            offset,
+            text: String::from_utf8_lossy(bytes),
+            has_nul: bytes.contains(&0),
+        }))
+    }
+}
+
+/// The byte range of the line in `haystack` that contains the byte at
+/// `at`, without its `\n` terminator. A `\r` before the `\n` is left for
+/// the caller to deal with.
+pub fn line_bounds(haystack: &[u8], at: usize) -> Range<usize> {
+    let start = memchr::memrchr(b'\n', &haystack[..at]).map_or(0, |i| i + 1);
+    let end = memchr::memchr(b'\n', &haystack[at..]).map_or(haystack.len(), |i| at + i);
+    start..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lines_with_numbers_and_offsets() {
+        let mut reader = LineReader::new(&b"one\r\ntwo\n\nthree"[..]);
+        let mut lines = Vec::new();
+
+        while let Some(line) = reader.next_line().unwrap() {
+            lines.push((line.number, line.offset, line.text.into_owned()));
+        }
+
+        assert_eq!(
+            vec![
+                (1, 0, "one".to_string()),
+                (2, 5, "two".to_string()),
+                (3, 9, String::new()),
+                (4, 10, "three".to_string()),
+            ],
+            lines
+        );
+    }
+
+    #[test]
+    fn decodes_invalid_utf8_lossily() {
+        let mut reader = LineReader::new(&b"caf\xe9 au lait\n"[..]);
+        let line = reader.next_line().unwrap().unwrap();
+
+        assert_eq!("caf\u{fffd} au lait", line.text);
+        assert!(!line.has_nul);
+    }
+
+    #[test]
+    fn detects_nul_bytes() {
+        let mut reader = LineReader::new(&b"text\nELF\0\x01\n"[..]);
+
+        assert!(reader.looks_binary().unwrap());
+        assert!(!reader.next_line().unwrap().unwrap().has_nul);
+        assert!(reader.next_line().unwrap().unwrap().has_nul);
+    }
+
+    #[test]
+    fn finds_the_line_around_a_byte() {
+        let haystack = b"one\ntwo\n\nthree";
+
+        assert_eq!(0..3, line_bounds(haystack, 1));
+        assert_eq!(4..7, line_bounds(haystack, 4));
+        assert_eq!(8..8, line_bounds(haystack, 8));
+        assert_eq!(9..14, line_bounds(haystack, 13));
+    }
+}
@@ -0,0 +1,735 @@
+use crate::fuzzy::FuzzyMatcher;
+use aho_corasick::{AhoCorasick, Input, MatchKind};
+use caseless::Caseless;
+use regex::{Regex, RegexBuilder};
+use std::cmp::Reverse;
+use std::iter;
+use std::ops::Range;
+
+/// Finds the query in a line.
+///
+/// A `Searcher` only ever talks to a `Matcher`, so it does not need to know
+/// how the query is interpreted. Matchers are shared between search
+/// threads, so they must be `Send + Sync`.
+pub trait Matcher: Send + Sync {
+    /// The byte range of the first match that starts at or after `start`.
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>>;
+
+    fn is_match(&self, line: &str) -> bool {
+        self.find_at(line, 0).is_some()
+    }
+
+    /// The byte ranges of all non-overlapping matches in `line`.
+    fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        let mut matches = Vec::new();
+        let mut start = Some(0);
+
+        while let Some(m) = start.and_then(|start| self.find_at(line, start)) {
+            start = next_start(line, &m);
+            matches.push(m);
+        }
+
+        matches
+    }
+
+    /// How far `line` is from matching exactly, or `None` if it does not
+    /// match. Only approximate matchers return anything but `Some(0)`.
+    fn distance(&self, line: &str) -> Option<u32> {
+        self.is_match(line).then_some(0)
+    }
+
+    /// A non-empty string that every match contains exactly as it is, if
+    /// there is one. Searchers use it to jump straight to the lines that
+    /// might match.
+    fn required_literal(&self) -> Option<&str> {
+        None
+    }
+
+    /// Replace every match in `line`, returning the new line and where each
+    /// replacement ended up in it.
+    ///
+    /// `template` is used as it is, unless the matcher knows about capture
+    /// groups.
+    fn replace_all(&self, line: &str, template: &str) -> (String, Vec<Range<usize>>) {
+        let matches = self.find_iter(line).into_iter().map(|span| (span, ()));
+        replace_each(line, matches, |(), out| out.push_str(template))
+    }
+}
+
+/// Where to look for the match after `m`. After an empty match, the next
+/// character is stepped over so that empty matches cannot loop.
+fn next_start(line: &str, m: &Range<usize>) -> Option<usize> {
+    if !m.is_empty() {
+        return Some(m.end);
+    }
+    line[m.end..].chars().next().map(|c| m.end + c.len_utf8())
+}
+
+/// Copy `line` with each match replaced by what `expand` appends for it,
+/// returning the new line and where each replacement ended up in it.
+fn replace_each<T>(
+    line: &str,
+    matches: impl Iterator<Item = (Range<usize>, T)>,
+    mut expand: impl FnMut(T, &mut String),
+) -> (String, Vec<Range<usize>>) {
+    let mut out = String::with_capacity(line.len());
+    let mut replaced = Vec::new();
+    let mut last = 0;
+
+    for (span, m) in matches {
+        out.push_str(&line[last..span.start]);
+        let start = out.len();
+        expand(m, &mut out);
+        replaced.push(start..out.len());
+        last = span.end;
+    }
+
+    out.push_str(&line[last..]);
+    (out, replaced)
+}
+
+/// How the query should be interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mode {
+    /// The query is a plain string (`-F`).
+    #[default]
+    Literal,
+    /// The query is a regular expression (`-E`).
+    Regex,
+}
+
+/// Settings that change what counts as a match, whatever the mode.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MatchOptions {
+    pub ignore_case: bool,
+    /// Only match whole words (`-w`).
+    pub word: bool,
+    /// Only match whole lines (`-x`).
+    pub line: bool,
+    /// Match within this many edits of a literal query (`--fuzzy`).
+    pub fuzzy: Option<u32>,
+}
+
+impl Mode {
+    /// Build a matcher that matches any of `patterns`.
+    ///
+    /// With no patterns at all nothing matches, as with an empty `-f` file.
+    pub fn compile(
+        self,
+        patterns: &[String],
+        options: MatchOptions,
+    ) -> Result<Box<dyn Matcher>, String> {
+        if patterns.is_empty() {
+            return Ok(Box::new(MultiLiteralMatcher::new(patterns)?));
+        }
+
+        if let Some(max_edits) = options.fuzzy {
+            let [query] = patterns else {
+                return Err("--fuzzy takes a single pattern".to_string());
+            };
+            return Ok(Box::new(FuzzyMatcher::new(
+                query,
+                max_edits,
+                options.ignore_case,
+            )));
+        }
+
+        if self == Mode::Literal {
+            // Aho-Corasick and the regex engine can only fold one character
+            // to one, so every literal search that ignores case goes through
+            // the same full folding instead.
+            if options.ignore_case {
+                return Ok(Box::new(FoldedMatcher::new(
+                    patterns,
+                    options.word,
+                    options.line,
+                )?));
+            }
+            if !options.word && !options.line {
+                if let [query] = patterns {
+                    return Ok(Box::new(LiteralMatcher::new(query, false)));
+                }
+                return Ok(Box::new(MultiLiteralMatcher::new(patterns)?));
+            }
+        }
+
+        let alternatives = patterns
+            .iter()
+            .map(|query| match self {
+                Mode::Literal => Ok({
+                    let escaped = regex::escape(query);
+                    if options.word && !options.line {
+                        // `\b` only means "word boundary" next to a word
+                        // character; next to anything else we need `\B` to
+                        // get the same "not inside a word" behaviour as grep.
+                        let before = boundary(query.chars().next());
+                        let after = boundary(query.chars().next_back());
+                        format!("{before}{escaped}{after}")
+                    } else {
+                        escaped
+                    }
+                }),
+                Mode::Regex => {
+                    let pattern = parse(query, options.ignore_case)?;
+                    Ok(if options.word && !options.line {
+                        format!(r"\b(?:{pattern})\b")
+                    } else {
+                        format!("(?:{pattern})")
+                    })
+                }
+            })
+            .collect::<Result<Vec<String>, String>>()?;
+        let pattern = alternatives.join("|");
+
+        let pattern = if options.line {
+            format!("^(?:{pattern})$")
+        } else {
+            pattern
+        };
+
+        let mut matcher = RegexMatcher::new(&pattern, options.ignore_case)?;
+        matcher.expand_captures = self == Mode::Regex;
+        Ok(Box::new(matcher))
+    }
+}
+
+/// Parse `query` as a regular expression on its own, so that it is checked
+/// and any error is reported against what was typed rather than the
+/// pattern it becomes part of, and return it written out again in a form
+/// that means the same wherever it is put, even after `(?x)` or an
+/// unclosed comment.
+fn parse(query: &str, ignore_case: bool) -> Result<String, String> {
+    let hir = regex_syntax::ParserBuilder::new()
+        .case_insensitive(ignore_case)
+        .build()
+        .parse(query)
+        .map_err(|e| format!("invalid regular expression: {e}"))?;
+
+    Ok(hir.to_string())
+}
+
+fn boundary(edge: Option<char>) -> &'static str {
+    match edge {
+        Some(c) if is_word_char(c) => r"\b",
+        _ => r"\B",
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `query` has an uppercase letter, for `--smart-case`.
+///
+/// In a regular expression, escapes such as `\W`, `\p{Lu}` or `\x{41}` and
+/// group names such as `(?P<Name>...)` are syntax rather than text to
+/// match, so their letters are skipped.
+pub fn has_uppercase(query: &str, mode: Mode) -> bool {
+    if mode == Mode::Literal {
+        return query.chars().any(char::is_uppercase);
+    }
+
+    let mut chars = query.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                // `\pL`, `\p{Lu}`, `\x41`, `\x{41}`, `\u0041` and so on take a
+                // braced argument, or else a fixed number of characters. The
+                // character after any other backslash is a one-letter escape
+                // like `\W`, or punctuation, which has no case.
+                let len = match chars.next() {
+                    Some('p' | 'P') => 1,
+                    Some('x') => 2,
+                    Some('u') => 4,
+                    Some('U') => 8,
+                    _ => 0,
+                };
+                if len > 0 && chars.next_if_eq(&'{').is_some() {
+                    chars.by_ref().find(|&c| c == '}');
+                } else {
+                    for _ in 0..len {
+                        chars.next_if(char::is_ascii_alphanumeric);
+                    }
+                }
+            }
+            '(' if chars.next_if_eq(&'?').is_some() => {
+                chars.next_if_eq(&'P');
+                if chars.next_if_eq(&'<').is_some() {
+                    chars.by_ref().find(|&c| c == '>');
+                }
+            }
+            c if c.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+/// The Unicode default case folding of `c`, which may be several characters
+/// (`ß` folds to `ss`).
+///
+/// This is the one folding every matcher uses to ignore case, apart from
+/// regular expressions: the regex engine can only use simple case folding,
+/// where one character only ever matches one character.
+pub(crate) fn fold(c: char) -> impl Iterator<Item = char> {
+    iter::once(c).default_case_fold()
+}
+
+/// `line` with every character case folded, and for each byte of that
+/// the offset in `line` of the character it came from, plus `line.len()`
+/// for the end.
+fn fold_line(line: &str) -> (String, Vec<usize>) {
+    let mut folded = String::with_capacity(line.len());
+    let mut offsets = Vec::with_capacity(line.len() + 1);
+
+    for (i, c) in line.char_indices() {
+        folded.extend(fold(c));
+        offsets.resize(folded.len(), i);
+    }
+    offsets.push(line.len());
+
+    (folded, offsets)
+}
+
+/// Matches the query as a plain string.
+///
+/// Ignoring case uses Unicode full case folding, so `STRASSE` matches
+/// `straße`. Folding is the default, locale-independent one: the Turkish
+/// dotless `ı` only matches itself, while `İ` matches `i̇`.
+pub struct LiteralMatcher {
+    query: String,
+    /// Does the matching when ignoring case.
+    folded: Option<FoldedMatcher>,
+}
+
+impl LiteralMatcher {
+    pub fn new(query: &str, ignore_case: bool) -> LiteralMatcher {
+        LiteralMatcher {
+            query: query.to_string(),
+            // One pattern is never too many for Aho-Corasick.
+            folded: ignore_case.then(|| {
+                FoldedMatcher::new(&[query.to_string()], false, false)
+                    .expect("a single pattern fits")
+            }),
+        }
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        match &self.folded {
+            Some(folded) => folded.find_at(line, start),
+            None => line[start..]
+                .find(&self.query)
+                .map(|i| start + i..start + i + self.query.len()),
+        }
+    }
+
+    fn required_literal(&self) -> Option<&str> {
+        (self.folded.is_none() && !self.query.is_empty()).then_some(self.query.as_str())
+    }
+}
+
+/// Matches any of several plain strings ignoring case by Unicode full case
+/// folding, optionally only as whole words (`-w`) or whole lines (`-x`). At
+/// any position the longest string wins.
+///
+/// Each line is folded once, and one Aho-Corasick automaton of the folded
+/// strings finds every place any of them occurs. A match must start and end
+/// where the folding of some character of the line does, so `s` does not
+/// match half of the `ss` that `ß` folds to.
+pub struct FoldedMatcher {
+    automaton: AhoCorasick,
+    word: bool,
+    line: bool,
+}
+
+impl FoldedMatcher {
+    pub fn new(patterns: &[String], word: bool, line: bool) -> Result<FoldedMatcher, String> {
+        let folded = patterns
+            .iter()
+            .map(|query| query.chars().flat_map(fold).collect::<String>());
+        // Overlapping searches need the standard match kind; which match
+        // wins is decided afterwards.
+        let automaton = AhoCorasick::new(folded).map_err(|e| format!("too many patterns: {e}"))?;
+
+        Ok(FoldedMatcher {
+            automaton,
+            word,
+            line,
+        })
+    }
+
+    /// Every span of `line` that some pattern matches, leftmost first and
+    /// then longest first.
+    fn candidates(&self, line: &str) -> Vec<Range<usize>> {
+        let (folded, offsets) = fold_line(line);
+        let aligned = |i: usize| i == 0 || i == folded.len() || offsets[i] != offsets[i - 1];
+
+        let mut spans: Vec<Range<usize>> = self
+            .automaton
+            .find_overlapping_iter(&folded)
+            .filter(|m| aligned(m.start()) && aligned(m.end()))
+            .map(|m| offsets[m.start()]..offsets[m.end()])
+            .filter(|span| self.fits(line, span))
+            .collect();
+
+        spans.sort_unstable_by_key(|span| (span.start, Reverse(span.end)));
+        spans
+    }
+
+    fn fits(&self, line: &str, span: &Range<usize>) -> bool {
+        if self.line {
+            return *span == (0..line.len());
+        }
+
+        // Like grep, a whole word is one with no word characters either side
+        // of it, whatever the query itself starts or ends with.
+        !self.word
+            || !(line[..span.start]
+                .chars()
+                .next_back()
+                .is_some_and(is_word_char)
+                || line[span.end..].chars().next().is_some_and(is_word_char))
+    }
+}
+
+impl Matcher for FoldedMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.candidates(line)
+            .into_iter()
+            .find(|span| span.start >= start)
+    }
+
+    /// Folds the line once for all of its matches.
+    fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        let mut matches = Vec::new();
+        let mut start = 0;
+
+        for span in self.candidates(line) {
+            if span.start < start {
+                continue;
+            }
+            let next = next_start(line, &span);
+            matches.push(span);
+            match next {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+
+        matches
+    }
+}
+
+/// Matches any of several plain strings at once with Aho-Corasick, rather
+/// than searching the line once per string. At any position the longest
+/// string wins.
+pub struct MultiLiteralMatcher {
+    automaton: AhoCorasick,
+}
+
+impl MultiLiteralMatcher {
+    pub fn new(patterns: &[String]) -> Result<MultiLiteralMatcher, String> {
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(patterns)
+            .map_err(|e| format!("too many patterns: {e}"))?;
+
+        Ok(MultiLiteralMatcher { automaton })
+    }
+}
+
+impl Matcher for MultiLiteralMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.automaton
+            .find(Input::new(line).range(start..))
+            .map(|m| m.range())
+    }
+}
+
+pub struct RegexMatcher {
+    regex: Regex,
+    /// Whether replacements expand capture groups, which they do not when
+    /// the pattern was built from literal queries.
+    expand_captures: bool,
+}
+
+impl RegexMatcher {
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<RegexMatcher, String> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()
+            .map_err(|e| format!("invalid regular expression: {e}"))?;
+
+        Ok(RegexMatcher {
+            regex,
+            expand_captures: true,
+        })
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.regex.find_at(line, start).map(|m| m.range())
+    }
+
+    /// Expands `$1`, `${name}` and `$0` in `template` from the captures of
+    /// each match; `$$` is a literal `$`.
+    fn replace_all(&self, line: &str, template: &str) -> (String, Vec<Range<usize>>) {
+        if !self.expand_captures {
+            let matches = self.find_iter(line).into_iter().map(|span| (span, ()));
+            return replace_each(line, matches, |(), out| out.push_str(template));
+        }
+
+        // Expand from the very search that found each match, stepping from
+        // one to the next as `find_iter` does.
+        let mut start = Some(0);
+        let matches = iter::from_fn(|| {
+            let captures = self.regex.captures_at(line, start?)?;
+            let span = captures.get(0)?.range();
+            start = next_start(line, &span);
+            Some((span, captures))
+        });
+        replace_each(line, matches, |captures, out| {
+            captures.expand(template, out)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find_all(query: &str, mode: Mode, options: MatchOptions, line: &str) -> Vec<Range<usize>> {
+        find_any(&[query], mode, options, line)
+    }
+
+    fn find_any(
+        patterns: &[&str],
+        mode: Mode,
+        options: MatchOptions,
+        line: &str,
+    ) -> Vec<Range<usize>> {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+        mode.compile(&patterns, options).unwrap().find_iter(line)
+    }
+
+    fn compile(query: &str, mode: Mode, options: MatchOptions) -> Box<dyn Matcher> {
+        mode.compile(&[query.to_string()], options).unwrap()
+    }
+
+    #[test]
+    fn literal_ignore_case_reports_original_spans() {
+        let options = MatchOptions {
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+
+        assert_eq!(
+            vec![0..4, 12..16],
+            find_all("rUsT", Mode::Literal, options, "Rust and a TRUST")
+        );
+        assert_eq!(vec![2..5], find_all("éa", Mode::Literal, options, "xxÉA"));
+    }
+
+    #[test]
+    fn literal_ignore_case_folds_unicode() {
+        let options = MatchOptions {
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+
+        assert_eq!(
+            vec![0..7],
+            find_all("STRASSE", Mode::Literal, options, "straße")
+        );
+        assert_eq!(
+            vec![4..11],
+            find_all("straße", Mode::Literal, options, "Die STRASSE")
+        );
+        assert_eq!(
+            vec![0..9],
+            find_all("i\u{307}stanbul", Mode::Literal, options, "İstanbul")
+        );
+        assert!(find_all("ıstanbul", Mode::Literal, options, "Istanbul").is_empty());
+        // "s" would only match half of what "ß" folds to.
+        assert!(find_all("s", Mode::Literal, options, "ß").is_empty());
+    }
+
+    #[test]
+    fn uppercase_ignores_regex_escapes() {
+        assert!(has_uppercase("Rust", Mode::Literal));
+        assert!(!has_uppercase("rust", Mode::Literal));
+        assert!(has_uppercase(r"\W", Mode::Literal));
+        assert!(!has_uppercase(r"\w+\S", Mode::Regex));
+        assert!(has_uppercase(r"\wR", Mode::Regex));
+        assert!(!has_uppercase(r"\p{Lu}x\pL\PN", Mode::Regex));
+        assert!(!has_uppercase(
+            r"\x{1F980}\xAB\u00C9\U0001F980",
+            Mode::Regex
+        ));
+        assert!(!has_uppercase(r"(?P<Year>\d+)-(?<Month>\d+)", Mode::Regex));
+        assert!(has_uppercase(r"\p{Lu}X", Mode::Regex));
+        assert!(has_uppercase(r"\.Rs", Mode::Regex));
+    }
+
+    #[test]
+    fn every_literal_matcher_folds_case_the_same_way() {
+        let ignore_case = MatchOptions {
+            ignore_case: true,
+            ..MatchOptions::default()
+        };
+        let word = MatchOptions {
+            word: true,
+            ..ignore_case
+        };
+        let line = MatchOptions {
+            line: true,
+            ..ignore_case
+        };
+
+        let single = find_all("STRASSE", Mode::Literal, ignore_case, "die straße");
+        let several = find_any(
+            &["STRASSE", "weg"],
+            Mode::Literal,
+            ignore_case,
+            "die straße",
+        );
+        let whole_word = find_all("STRASSE", Mode::Literal, word, "die straße");
+        let whole_line = find_all("DIE STRASSE", Mode::Literal, line, "die straße");
+        assert_eq!(vec![4..11], single);
+        assert_eq!(single, several);
+        assert_eq!(single, whole_word);
+        assert_eq!(vec![0..11], whole_line);
+
+        // "af" ends halfway through what "ﬃ" folds to, so "a" wins.
+        assert_eq!(
+            vec![0..1],
+            find_any(&["a", "AF"], Mode::Literal, ignore_case, "aﬃ")
+        );
+        assert_eq!(
+            vec![1..4],
+            find_any(&["FFI", "f"], Mode::Literal, ignore_case, "aﬃ")
+        );
+        assert!(find_all("STRASSE", Mode::Literal, word, "hauptstraße").is_empty());
+        assert!(find_all("STRASSE", Mode::Literal, line, "die straße").is_empty());
+        assert_eq!(vec![4..6], find_all("+1", Mode::Literal, word, "a+1 +1"));
+    }
+
+    #[test]
+    fn word_option_requires_word_boundaries() {
+        let options = MatchOptions {
+            word: true,
+            ..MatchOptions::default()
+        };
+
+        assert_eq!(
+            vec![7..10],
+            find_all("foo", Mode::Literal, options, "foobar foo")
+        );
+        assert_eq!(vec![4..6], find_all("+1", Mode::Literal, options, "a+1 +1"));
+        assert_eq!(
+            vec![0..3],
+            find_all("fo+", Mode::Regex, options, "foo fooo_")
+        );
+    }
+
+    #[test]
+    fn line_option_requires_whole_line() {
+        let options = MatchOptions {
+            line: true,
+            ..MatchOptions::default()
+        };
+
+        assert!(compile("a.b", Mode::Literal, options).is_match("a.b"));
+        assert!(!compile("a.b", Mode::Literal, options).is_match("a.bc"));
+        assert!(!compile("a|b", Mode::Regex, options).is_match("ab"));
+    }
+
+    #[test]
+    fn matches_any_of_several_patterns() {
+        let options = MatchOptions::default();
+        let line = "the cat sat on the category";
+
+        assert_eq!(
+            vec![4..7, 8..11, 19..27],
+            find_any(&["cat", "sat", "category"], Mode::Literal, options, line)
+        );
+        assert_eq!(
+            vec![4..7, 8..11, 19..22],
+            find_any(&["c.t", "s.t"], Mode::Regex, options, line)
+        );
+        assert_eq!(
+            vec![0..3, 4..7, 15..18],
+            find_any(
+                &["cat", "THE"],
+                Mode::Literal,
+                MatchOptions {
+                    ignore_case: true,
+                    word: true,
+                    ..options
+                },
+                "The cat sat on the category"
+            )
+        );
+        assert!(find_any(&[], Mode::Regex, options, line).is_empty());
+    }
+
+    #[test]
+    fn regex_patterns_are_parsed_one_at_a_time() {
+        let compile_all = |patterns: &[&str], options| {
+            let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+            Mode::Regex.compile(&patterns, options).map(|_| ())
+        };
+        let word = MatchOptions {
+            word: true,
+            ..MatchOptions::default()
+        };
+
+        let err = compile_all(&["a)(b"], MatchOptions::default()).unwrap_err();
+        assert!(err.contains("a)(b") && !err.contains("(?:"), "{err}");
+        assert!(compile_all(&["a)|(x", "zzz"], MatchOptions::default()).is_err());
+        assert!(compile_all(&["a)(b"], word).is_err());
+
+        assert_eq!(
+            vec![2..3],
+            find_any(&["(?x) b # comment", "zzz"], Mode::Regex, word, "a b c")
+        );
+        let matcher = compile(r"(?P<x>a)-(b)", Mode::Regex, MatchOptions::default());
+        assert_eq!("b-a", matcher.replace_all("a-b", "$2-$x").0);
+    }
+
+    #[test]
+    fn replacements_expand_captures_only_in_regex_mode() {
+        let word = MatchOptions {
+            word: true,
+            ..MatchOptions::default()
+        };
+
+        let matcher = compile(r"(\w+)@(\w+)", Mode::Regex, MatchOptions::default());
+        assert_eq!(
+            ("mail home.ann or work.bob".to_string(), vec![5..13, 17..25]),
+            matcher.replace_all("mail ann@home or bob@work", "$2.$1")
+        );
+        assert_eq!(
+            ("<a><>b<>".to_string(), vec![0..3, 3..5, 6..8]),
+            compile("a*", Mode::Regex, MatchOptions::default()).replace_all("ab", "<$0>")
+        );
+
+        let matcher = compile("to", Mode::Literal, word);
+        assert_eq!(
+            "$1 be or not $1 be",
+            matcher.replace_all("to be or not to be", "$1").0
+        );
+    }
+
+    #[test]
+    fn empty_matches_do_not_loop() {
+        assert_eq!(
+            vec![0..0, 1..1, 3..3],
+            find_all("", Mode::Regex, MatchOptions::default(), "aé")
+        );
+    }
+}
@@ -0,0 +1,611 @@
+use crate::lines::{self, LineReader, BINARY_PEEK};
+use crate::matcher::{self, MatchOptions, Matcher, Mode};
+use crate::sink::{Match, Sink};
+use memchr::memmem::Finder;
+use std::collections::VecDeque;
+use std::io::{self, BufRead};
+
+/// How to treat case when matching.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    Insensitive,
+    /// Insensitive unless a pattern contains an uppercase letter.
+    Smart,
+}
+
+/// Builds a [`Searcher`].
+///
+/// ```
+/// use searcher::{CaseMode, Searcher};
+///
+/// let searcher = Searcher::builder()
+///     .pattern("rust")
+///     .case(CaseMode::Insensitive)
+///     .after_context(1)
+///     .build()
+///     .unwrap();
+///
+/// let mut lines = Vec::new();
+/// searcher.search_reader("Rust:\nsafe, fast\n".as_bytes(), &mut lines).unwrap();
+/// assert_eq!(vec!["Rust:"], lines);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SearcherBuilder {
+    patterns: Vec<String>,
+    mode: Mode,
+    case: CaseMode,
+    word: bool,
+    line: bool,
+    fuzzy: Option<u32>,
+    invert_match: bool,
+    before_context: usize,
+    after_context: usize,
+    text: bool,
+}
+
+impl SearcherBuilder {
+    pub fn new() -> SearcherBuilder {
+        SearcherBuilder::default()
+    }
+
+    /// Add a pattern; lines matching any pattern are selected.
+    pub fn pattern(mut self, pattern: impl Into<String>) -> SearcherBuilder {
+        self.patterns.push(pattern.into());
+        self
+    }
+
+    pub fn patterns<I, S>(mut self, patterns: I) -> SearcherBuilder
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.patterns.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn mode(mut self, mode: Mode) -> SearcherBuilder {
+        self.mode = mode;
+        self
+    }
+
+    pub fn case(mut self, case: CaseMode) -> SearcherBuilder {
+        self.case = case;
+        self
+    }
+
+    /// Only match whole words.
+    pub fn word(mut self, yes: bool) -> SearcherBuilder {
+        self.word = yes;
+        self
+    }
+
+    /// Only match whole lines.
+    pub fn line(mut self, yes: bool) -> SearcherBuilder {
+        self.line = yes;
+        self
+    }
+
+    /// Match a literal pattern within `max_edits` edits.
+    pub fn fuzzy(mut self, max_edits: Option<u32>) -> SearcherBuilder {
+        self.fuzzy = max_edits;
+        self
+    }
+
+    /// Select the lines that do not match instead.
+    pub fn invert_match(mut self, yes: bool) -> SearcherBuilder {
+        self.invert_match = yes;
+        self
+    }
+
+    pub fn before_context(mut self, lines: usize) -> SearcherBuilder {
+        self.before_context = lines;
+        self
+    }
+
+    pub fn after_context(mut self, lines: usize) -> SearcherBuilder {
+        self.after_context = lines;
+        self
+    }
+
+    /// Search input that looks binary as if it were text.
+    pub fn text(mut self, yes: bool) -> SearcherBuilder {
+        self.text = yes;
+        self
+    }
+
+    pub fn build(self) -> Result<Searcher, String> {
+        let ignore_case = match self.case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !self
+                .patterns
+                .iter()
+                .any(|pattern| matcher::has_uppercase(pattern, self.mode)),
+        };
+
+        let options = MatchOptions {
+            ignore_case,
+            word: self.word,
+            line: self.line,
+            fuzzy: self.fuzzy,
+        };
+        let matcher = self.mode.compile(&self.patterns, options)?;
+
+        // Lines are decoded lossily before matching, so a pattern with a
+        // replacement character in it can match where the raw bytes do not.
+        let finder = matcher
+            .required_literal()
+            .filter(|literal| !literal.contains(char::REPLACEMENT_CHARACTER))
+            .map(|literal| Finder::new(literal).into_owned());
+
+        Ok(Searcher {
+            matcher,
+            finder,
+            ignore_case,
+            invert_match: self.invert_match,
+            before_context: self.before_context,
+            after_context: self.after_context,
+            text: self.text,
+        })
+    }
+}
+
+/// Searches input line by line and reports what it finds to a [`Sink`].
+pub struct Searcher {
+    matcher: Box<dyn Matcher>,
+    /// Finds the matcher's required literal, to skip lines without it.
+    finder: Option<Finder<'static>>,
+    ignore_case: bool,
+    invert_match: bool,
+    before_context: usize,
+    after_context: usize,
+    text: bool,
+}
+
+/// How far a search from one occurrence of the required literal to the
+/// next has got through its input.
+struct Scan {
+    /// Byte offset in the input of the block being searched.
+    offset: u64,
+    /// Lines of input before that block.
+    lines: u64,
+    count: u64,
+    binary: bool,
+}
+
+/// A line held back in case it turns out to be context before a match.
+struct Held {
+    number: u64,
+    offset: u64,
+    text: String,
+}
+
+impl Searcher {
+    pub fn builder() -> SearcherBuilder {
+        SearcherBuilder::new()
+    }
+
+    pub fn matcher(&self) -> &dyn Matcher {
+        self.matcher.as_ref()
+    }
+
+    /// Whether case is ignored, once `CaseMode::Smart` has been decided.
+    pub fn ignore_case(&self) -> bool {
+        self.ignore_case
+    }
+
+    pub fn invert_match(&self) -> bool {
+        self.invert_match
+    }
+
+    pub fn before_context(&self) -> usize {
+        self.before_context
+    }
+
+    pub fn after_context(&self) -> usize {
+        self.after_context
+    }
+
+    /// Whether binary input is searched as text.
+    pub fn text(&self) -> bool {
+        self.text
+    }
+
+    /// Search `reader` and return the number of lines selected. Input is
+    /// read a block at a time, so it never has to fit in memory.
+    ///
+    /// When every match has to contain some literal string, this jumps from
+    /// one occurrence of it to the next in each block with a vectorised
+    /// substring search and only splits out the lines around them, rather
+    /// than matching each line in turn. Either way the sink sees the same
+    /// results.
+    pub fn search_reader(&self, reader: impl BufRead, sink: &mut impl Sink) -> io::Result<u64> {
+        if let Some(finder) = self.literal_finder() {
+            return self.scan_reader(finder, reader, sink);
+        }
+
+        let mut lines = LineReader::new(reader);
+        let mut binary = !self.text && lines.looks_binary()?;
+        let mut count = 0;
+
+        let mut before: VecDeque<Held> = VecDeque::with_capacity(self.before_context);
+        let mut after_remaining = 0;
+        let mut last_reported: Option<u64> = None;
+        let has_context = self.before_context > 0 || self.after_context > 0;
+
+        while let Some(line) = lines.next_line()? {
+            binary = binary || (!self.text && line.has_nul);
+
+            let spans = self.matcher.find_iter(&line.text);
+            if spans.is_empty() != self.invert_match {
+                if binary {
+                    continue;
+                }
+                if after_remaining > 0 {
+                    after_remaining -= 1;
+                    last_reported = Some(line.number);
+                    if !sink.context(line.number, line.offset, &line.text)? {
+                        break;
+                    }
+                } else if self.before_context > 0 {
+                    if before.len() == self.before_context {
+                        before.pop_front();
+                    }
+                    before.push_back(Held {
+                        number: line.number,
+                        offset: line.offset,
+                        text: line.text.into_owned(),
+                    });
+                }
+                continue;
+            }
+            count += 1;
+
+            let m = Match {
+                line_number: line.number,
+                byte_offset: line.offset,
+                line: &line.text,
+                spans: if self.invert_match { Vec::new() } else { spans },
+            };
+
+            if binary {
+                if !sink.binary_match(&m)? {
+                    break;
+                }
+                continue;
+            }
+
+            let first = before.front().map_or(line.number, |held| held.number);
+            if has_context
+                && last_reported.is_some_and(|last| last + 1 < first)
+                && !sink.context_break()?
+            {
+                break;
+            }
+            for held in before.drain(..) {
+                if !sink.context(held.number, held.offset, &held.text)? {
+                    return self.finish(sink, count, binary);
+                }
+            }
+
+            if !sink.matched(&m)? {
+                break;
+            }
+
+            last_reported = Some(line.number);
+            after_remaining = self.after_context;
+        }
+
+        self.finish(sink, count, binary)
+    }
+
+    /// Search `haystack`, which holds all of the input, as one block, and
+    /// return the number of lines selected.
+    pub fn search_slice(&self, haystack: &[u8], sink: &mut impl Sink) -> io::Result<u64> {
+        self.search_reader(haystack, sink)
+    }
+
+    /// The finder for the required literal, if it can stand in for matching
+    /// every line: lines without it are neither selected nor context.
+    fn literal_finder(&self) -> Option<&Finder<'static>> {
+        self.finder
+            .as_ref()
+            .filter(|_| !self.invert_match && self.before_context == 0 && self.after_context == 0)
+    }
+
+    /// Search the whole lines in each block that `reader` buffers with
+    /// `scan_block`, carrying a line that runs past the end of one block
+    /// over to the next.
+    fn scan_reader(
+        &self,
+        finder: &Finder,
+        mut reader: impl BufRead,
+        sink: &mut impl Sink,
+    ) -> io::Result<u64> {
+        let peek = reader.fill_buf()?;
+        let mut scan = Scan {
+            offset: 0,
+            lines: 0,
+            count: 0,
+            binary: !self.text && memchr::memchr(0, &peek[..peek.len().min(BINARY_PEEK)]).is_some(),
+        };
+        let mut carried = Vec::new();
+
+        loop {
+            let block = reader.fill_buf()?;
+            if block.is_empty() {
+                if !carried.is_empty() {
+                    self.scan_block(finder, &carried, &mut scan, sink)?;
+                }
+                break;
+            }
+
+            let len = block.len();
+            let more = match memchr::memrchr(b'\n', block) {
+                // Search the reader's own buffer where we can.
+                Some(end) if carried.is_empty() => {
+                    let more = self.scan_block(finder, &block[..=end], &mut scan, sink)?;
+                    reader.consume(end + 1);
+                    more
+                }
+                Some(end) => {
+                    carried.extend_from_slice(&block[..=end]);
+                    reader.consume(end + 1);
+                    let more = self.scan_block(finder, &carried, &mut scan, sink)?;
+                    carried.clear();
+                    more
+                }
+                None => {
+                    carried.extend_from_slice(block);
+                    reader.consume(len);
+                    true
+                }
+            };
+            if !more {
+                break;
+            }
+        }
+
+        self.finish(sink, scan.count, scan.binary)
+    }
+
+    /// Report the lines of `block` that match, checking only those with the
+    /// required literal in them, and return false if the sink wants no
+    /// more. `block` holds whole lines: it ends after a `\n`, or at the end
+    /// of the input.
+    fn scan_block(
+        &self,
+        finder: &Finder,
+        block: &[u8],
+        scan: &mut Scan,
+        sink: &mut impl Sink,
+    ) -> io::Result<bool> {
+        // Line terminators counted, and bytes checked for NUL, so far.
+        let mut newlines = 0;
+        let mut counted = 0;
+        let mut checked = 0;
+        let mut at = 0;
+
+        while let Some(found) = block.get(at..).and_then(|rest| finder.find(rest)) {
+            let bounds = lines::line_bounds(block, at + found);
+            at = bounds.end + 1;
+
+            newlines += memchr::memchr_iter(b'\n', &block[counted..bounds.start]).count();
+            counted = bounds.start;
+            if !self.text && !scan.binary {
+                scan.binary = memchr::memchr(0, &block[checked..bounds.end]).is_some();
+                checked = bounds.end;
+            }
+
+            let bytes = &block[bounds.clone()];
+            let text = String::from_utf8_lossy(bytes.strip_suffix(b"\r").unwrap_or(bytes));
+            let spans = self.matcher.find_iter(&text);
+            if spans.is_empty() {
+                continue;
+            }
+            scan.count += 1;
+
+            let m = Match {
+                line_number: scan.lines + newlines as u64 + 1,
+                byte_offset: scan.offset + bounds.start as u64,
+                line: &text,
+                spans,
+            };
+            let more = if scan.binary {
+                sink.binary_match(&m)?
+            } else {
+                sink.matched(&m)?
+            };
+            if !more {
+                return Ok(false);
+            }
+        }
+
+        // Like `search_reader`, report binary input found after the last
+        // match too.
+        scan.binary = scan.binary || (!self.text && memchr::memchr(0, &block[checked..]).is_some());
+        newlines += memchr::memchr_iter(b'\n', &block[counted..]).count();
+        scan.lines += newlines as u64;
+        scan.offset += block.len() as u64;
+        Ok(true)
+    }
+
+    fn finish(&self, sink: &mut impl Sink, count: u64, binary: bool) -> io::Result<u64> {
+        sink.finish(count, binary)?;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records everything it is told, in grep's format.
+    #[derive(Default)]
+    struct Transcript(String);
+
+    impl Sink for Transcript {
+        fn matched(&mut self, m: &Match) -> io::Result<bool> {
+            self.0 += &format!("{}:{}\n", m.line_number, m.line);
+            Ok(true)
+        }
+
+        fn context(&mut self, line_number: u64, _: u64, line: &str) -> io::Result<bool> {
+            self.0 += &format!("{line_number}-{line}\n");
+            Ok(true)
+        }
+
+        fn context_break(&mut self) -> io::Result<bool> {
+            self.0 += "--\n";
+            Ok(true)
+        }
+
+        fn binary_match(&mut self, m: &Match) -> io::Result<bool> {
+            self.0 += &format!("binary {}\n", m.line_number);
+            Ok(false)
+        }
+
+        fn finish(&mut self, matches: u64, binary: bool) -> io::Result<()> {
+            self.0 += &format!("{matches} {binary}\n");
+            Ok(())
+        }
+    }
+
+    fn transcript(builder: SearcherBuilder, input: &[u8]) -> String {
+        let mut sink = Transcript::default();
+        builder
+            .build()
+            .unwrap()
+            .search_reader(input, &mut sink)
+            .unwrap();
+        sink.0
+    }
+
+    const INPUT: &[u8] = b"one\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+
+    #[test]
+    fn reports_context_and_breaks_between_groups() {
+        let builder = Searcher::builder()
+            .patterns(["two", "six"])
+            .before_context(1)
+            .after_context(1);
+
+        assert_eq!(
+            "1-one\n2:two\n3-three\n--\n5-five\n6:six\n7-seven\n2 false\n",
+            transcript(builder, INPUT)
+        );
+    }
+
+    #[test]
+    fn inverted_lines_have_no_spans() {
+        let searcher = Searcher::builder()
+            .pattern("e")
+            .invert_match(true)
+            .build()
+            .unwrap();
+        let mut lines = Vec::new();
+
+        assert_eq!(3, searcher.search_reader(INPUT, &mut lines).unwrap());
+        assert_eq!(vec!["two", "four", "six"], lines);
+    }
+
+    #[test]
+    fn stops_at_the_first_match_in_binary_input() {
+        let builder = Searcher::builder().pattern("match").after_context(1);
+
+        assert_eq!(
+            "binary 2\n1 true\n",
+            transcript(builder.clone(), b"ELF\0\nmatch\nmatch\n")
+        );
+        assert_eq!(
+            "2:match\n3-after\n1 false\n",
+            transcript(builder.text(true), b"ELF\0\nmatch\nafter\n")
+        );
+    }
+
+    #[test]
+    fn slices_give_the_same_results_as_readers() {
+        let inputs: [&[u8]; 5] = [
+            INPUT,
+            b"one two\r\ntwo\r\nthree\r\ntwo",
+            b"caf\xe9 two\n\ntwo\ttwo\n",
+            b"two\nbinary\0two\ntwo\n",
+            b"no match here\n",
+        ];
+
+        for builder in [
+            Searcher::builder().pattern("two"),
+            Searcher::builder().pattern("two").text(true),
+            Searcher::builder().pattern("two").after_context(1),
+            Searcher::builder()
+                .pattern("TWO")
+                .case(CaseMode::Insensitive),
+        ] {
+            let searcher = builder.build().unwrap();
+            for input in inputs {
+                let mut from_reader = Transcript::default();
+                let mut from_slice = Transcript::default();
+                searcher.search_reader(input, &mut from_reader).unwrap();
+                searcher.search_slice(input, &mut from_slice).unwrap();
+
+                assert_eq!(from_reader.0, from_slice.0, "{input:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn literal_scans_across_blocks_match_line_by_line_search() {
+        let mut input = b"caf\xe9 two\r\n\ntwo\ttwo\nlong line ".to_vec();
+        input.extend(b"x".repeat(100));
+        input.extend(b" two\nno\ntwo");
+
+        for capacity in [1, 3, 16, 1024] {
+            let reader = io::BufReader::with_capacity(capacity, &input[..]);
+            let literal = Searcher::builder().pattern("two").build().unwrap();
+            let mut scanned = Transcript::default();
+            literal.search_reader(reader, &mut scanned).unwrap();
+
+            // The regex matcher has no required literal to scan for.
+            let regex = Searcher::builder().pattern("two").mode(Mode::Regex);
+            assert_eq!(transcript(regex, &input), scanned.0, "{capacity}");
+        }
+    }
+
+    #[test]
+    fn slices_notice_binary_input_past_the_first_block() {
+        let mut input = b"two\n".to_vec();
+        input.extend(b"filler\n".repeat(BINARY_PEEK));
+        input.extend(b"NUL\0\ntwo\n");
+        let last = BINARY_PEEK + 3;
+
+        let searcher = Searcher::builder().pattern("two").build().unwrap();
+        let mut from_reader = Transcript::default();
+        let mut from_slice = Transcript::default();
+        searcher
+            .search_reader(&input[..], &mut from_reader)
+            .unwrap();
+        searcher.search_slice(&input, &mut from_slice).unwrap();
+
+        assert_eq!(format!("1:two\nbinary {last}\n2 true\n"), from_slice.0);
+        assert_eq!(from_reader.0, from_slice.0);
+    }
+
+    #[test]
+    fn smart_case_looks_at_every_pattern() {
+        let searcher = Searcher::builder()
+            .patterns(["rust", "safe"])
+            .case(CaseMode::Smart)
+            .build()
+            .unwrap();
+        assert!(searcher.ignore_case());
+
+        let searcher = Searcher::builder()
+            .patterns(["rust", "Safe"])
+            .case(CaseMode::Smart)
+            .build()
+            .unwrap();
+        assert!(!searcher.ignore_case());
+    }
+}
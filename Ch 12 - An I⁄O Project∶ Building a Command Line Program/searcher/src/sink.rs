@@ -0,0 +1,63 @@
+use std::io;
+use std::ops::Range;
+
+/// A line that was selected, and where it matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// 1-based line number.
+    pub line_number: u64,
+    /// Byte offset of the start of the line in the searched input.
+    pub byte_offset: u64,
+    /// The line, without its line terminator.
+    pub line: &'a str,
+    /// Byte ranges of each match within `line`. Empty when matches are
+    /// inverted, since a selected line then has nothing in it that matched.
+    pub spans: Vec<Range<usize>>,
+}
+
+/// Receives the results of a search from a [`Searcher`](crate::Searcher).
+///
+/// The searcher finds lines and works out context; the sink decides what
+/// to do with them, whether that is printing them in grep's format,
+/// counting them or collecting them. Every method but `matched` has a
+/// default that does nothing. Returning `Ok(false)` from any of them stops
+/// the search early, and errors stop it too.
+pub trait Sink {
+    /// A line was selected.
+    fn matched(&mut self, m: &Match) -> io::Result<bool>;
+
+    /// A line of context before or after a selected line.
+    fn context(&mut self, line_number: u64, byte_offset: u64, line: &str) -> io::Result<bool> {
+        let _ = (line_number, byte_offset, line);
+        Ok(true)
+    }
+
+    /// The next line given to the sink does not follow on from the last.
+    /// Only reported when context was asked for.
+    fn context_break(&mut self) -> io::Result<bool> {
+        Ok(true)
+    }
+
+    /// A line was selected in input that looks binary. This is called
+    /// instead of `matched`, and no context is reported for binary input.
+    /// By default the search stops at the first such line.
+    fn binary_match(&mut self, m: &Match) -> io::Result<bool> {
+        let _ = m;
+        Ok(false)
+    }
+
+    /// The search is over, having selected `matches` lines. `binary` is
+    /// whether the input looked binary.
+    fn finish(&mut self, matches: u64, binary: bool) -> io::Result<()> {
+        let _ = (matches, binary);
+        Ok(())
+    }
+}
+
+/// Collects every selected line, for tests and small tools.
+impl Sink for Vec<String> {
+    fn matched(&mut self, m: &Match) -> io::Result<bool> {
+        self.push(m.line.to_string());
+        Ok(true)
+    }
+}
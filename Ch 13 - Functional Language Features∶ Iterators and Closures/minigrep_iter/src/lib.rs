@@ -1,75 +1,25 @@
-use std::env;
-use std::error::Error;
-use std::fs;
+//! The iterator-based minigrep from chapter 13. It builds the same
+//! `Config` as minigrep, straight from the `env::args()` iterator, and
+//! searches with the same `run`, so the two never disagree.
 
-pub struct Config {
-    pub query: String,
-    pub file_path: String,
-    pub ignore_case: bool,
-}
-
-impl Config {
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next();
-
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
-        };
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file path"),
-        };
-
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
-
-        Ok(Config {
-            query,
-            file_path,
-            ignore_case,
-        })
-    }
-}
-
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
-
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
-    } else {
-        search(&config.query, &contents)
-    };
-
-    for line in results {
-        println!("{line}");
-    }
-
-    Ok(())
-}
-
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents
-        .lines()
-        .filter(|line| line.contains(query))
-        .collect()
-}
-
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    let mut results = Vec::new();
-
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
-        }
-    }
-
-    results
-}
+pub use minigrep::{run, Config};
+pub use searcher::{search, search_case_insensitive};
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn builds_config_from_an_iterator() {
+        let args = ["minigrep", "-i", "to", "poem.txt"]
+            .into_iter()
+            .map(String::from);
+        let config = Config::build(args).unwrap();
+
+        assert_eq!(vec![PathBuf::from("poem.txt")], config.paths);
+        assert!(config.searcher.matcher().is_match("TO"));
+    }
 
     #[test]
     fn case_sensitive() {
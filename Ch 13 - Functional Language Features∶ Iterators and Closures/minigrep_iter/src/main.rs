@@ -1,16 +1,23 @@
 use std::env;
+use std::io::{self, Write};
 use std::process;
 
 use minigrep_iter::Config;
 
 fn main() {
-    let config = Config::build(env::args()).unwrap_or_else(|err| {
-        eprintln!("Problem parsing arguments: {err}");
-        process::exit(1);
-    });
+    let config = Config::build(env::args()).unwrap_or_else(|err| err.exit());
 
-    if let Err(e) = minigrep_iter::run(config) {
-        eprintln!("Application error: {e}");
-        process::exit(1);
-    }
+    // Exit statuses follow grep, as minigrep's do.
+    let mut stdout = io::stdout().lock();
+    let code = match minigrep_iter::run(config, &mut stdout) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            2
+        }
+    };
+
+    let _ = stdout.flush();
+    process::exit(code);
 }
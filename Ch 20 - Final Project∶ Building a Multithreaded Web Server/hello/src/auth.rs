@@ -0,0 +1,291 @@
+use crate::http::{Request, Response};
+use argon2::{password_hash::PasswordHash, Argon2, PasswordVerifier};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{collections::HashMap, fs, io, path::Path};
+
+/// Users and password hashes read from an htpasswd-style file.
+///
+/// Each line is `user:hash`, where the hash is either bcrypt (`$2a$`, `$2b$`,
+/// `$2y$`) or argon2 in PHC string format (`$argon2id$...`). Blank lines and
+/// lines starting with `#` are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct Htpasswd {
+    users: HashMap<String, String>,
+}
+
+impl Htpasswd {
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Htpasswd> {
+        Htpasswd::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn parse(contents: &str) -> io::Result<Htpasswd> {
+        let mut users = HashMap::new();
+
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |message: &str| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("htpasswd line {}: {message}", number + 1),
+                )
+            };
+
+            let (user, hash) = line.split_once(':').ok_or_else(|| invalid("missing ':'"))?;
+            if !is_bcrypt(hash) && !hash.starts_with("$argon2") {
+                return Err(invalid("unsupported hash, expected bcrypt or argon2"));
+            }
+
+            users.insert(user.to_string(), hash.to_string());
+        }
+
+        Ok(Htpasswd { users })
+    }
+
+    pub fn verify(&self, user: &str, password: &str) -> bool {
+        let Some(hash) = self.users.get(user) else {
+            return false;
+        };
+
+        if is_bcrypt(hash) {
+            bcrypt::verify(password, hash).unwrap_or(false)
+        } else {
+            PasswordHash::new(hash)
+                .map(|hash| {
+                    Argon2::default()
+                        .verify_password(password.as_bytes(), &hash)
+                        .is_ok()
+                })
+                .unwrap_or(false)
+        }
+    }
+}
+
+fn is_bcrypt(hash: &str) -> bool {
+    ["$2a$", "$2b$", "$2y$"]
+        .iter()
+        .any(|prefix| hash.starts_with(prefix))
+}
+
+#[derive(Debug, Clone)]
+pub enum Scheme {
+    Basic(Htpasswd),
+    Bearer(Vec<String>),
+}
+
+/// Requires credentials for every path under `prefix`.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub prefix: String,
+    pub realm: String,
+    pub scheme: Scheme,
+}
+
+impl Rule {
+    pub fn basic(prefix: &str, realm: &str, users: Htpasswd) -> Rule {
+        Rule {
+            prefix: prefix.to_string(),
+            realm: realm.to_string(),
+            scheme: Scheme::Basic(users),
+        }
+    }
+
+    pub fn bearer(prefix: &str, realm: &str, tokens: Vec<String>) -> Rule {
+        Rule {
+            prefix: prefix.to_string(),
+            realm: realm.to_string(),
+            scheme: Scheme::Bearer(tokens),
+        }
+    }
+
+    fn covers(&self, path: &str) -> bool {
+        let prefix = self.prefix.trim_end_matches('/');
+        match path.strip_prefix(prefix) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/') || prefix.is_empty(),
+            None => false,
+        }
+    }
+
+    fn admits(&self, request: &Request) -> Result<(), Response> {
+        let authorization = request.header("Authorization");
+
+        match &self.scheme {
+            Scheme::Basic(users) => {
+                let admitted = authorization
+                    .and_then(|value| strip_scheme(value, "Basic"))
+                    .and_then(|encoded| STANDARD.decode(encoded).ok())
+                    .and_then(|decoded| String::from_utf8(decoded).ok())
+                    .is_some_and(|decoded| match decoded.split_once(':') {
+                        Some((user, password)) => users.verify(user, password),
+                        None => false,
+                    });
+
+                if admitted {
+                    Ok(())
+                } else {
+                    Err(unauthorized(format!(
+                        "Basic realm=\"{}\", charset=\"UTF-8\"",
+                        self.realm
+                    )))
+                }
+            }
+            Scheme::Bearer(tokens) => {
+                match authorization.and_then(|value| strip_scheme(value, "Bearer")) {
+                    Some(token) if tokens.iter().any(|t| constant_time_eq(t, token)) => Ok(()),
+                    Some(_) => Err(unauthorized(format!(
+                        "Bearer realm=\"{}\", error=\"invalid_token\"",
+                        self.realm
+                    ))),
+                    None => Err(unauthorized(format!("Bearer realm=\"{}\"", self.realm))),
+                }
+            }
+        }
+    }
+}
+
+/// The set of protected route prefixes for a server.
+#[derive(Debug, Clone, Default)]
+pub struct Auth {
+    rules: Vec<Rule>,
+}
+
+impl Auth {
+    pub fn new(rules: Vec<Rule>) -> Auth {
+        Auth { rules }
+    }
+
+    /// Check `request` against the most specific rule covering its path.
+    ///
+    /// Paths that no rule covers are public. On failure the `Err` holds the
+    /// 401 response to send back.
+    pub fn check(&self, request: &Request) -> Result<(), Response> {
+        let path = request.path.split('?').next().unwrap_or_default();
+
+        match self
+            .rules
+            .iter()
+            .filter(|rule| rule.covers(path))
+            .max_by_key(|rule| rule.prefix.trim_end_matches('/').len())
+        {
+            Some(rule) => rule.admits(request),
+            None => Ok(()),
+        }
+    }
+}
+
+fn strip_scheme<'a>(value: &'a str, scheme: &str) -> Option<&'a str> {
+    let (name, rest) = value.trim().split_once(' ')?;
+    if name.eq_ignore_ascii_case(scheme) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+fn unauthorized(challenge: String) -> Response {
+    Response::new(401, "UNAUTHORIZED", "Authentication required.\n")
+        .with_header("WWW-Authenticate", challenge)
+}
+
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use argon2::password_hash::{PasswordHasher, SaltString};
+
+    fn request(path: &str, authorization: Option<&str>) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: path.to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: authorization
+                .map(|value| vec![("Authorization".to_string(), value.to_string())])
+                .unwrap_or_default(),
+        }
+    }
+
+    fn basic(user: &str, password: &str) -> String {
+        format!("Basic {}", STANDARD.encode(format!("{user}:{password}")))
+    }
+
+    #[test]
+    fn verifies_bcrypt_and_argon2_hashes() {
+        let bcrypt = bcrypt::hash("hunter2", 4).unwrap();
+        let salt = SaltString::from_b64("c29tZXNhbHRzb21lc2FsdA").unwrap();
+        let argon2 = Argon2::default()
+            .hash_password(b"swordfish", &salt)
+            .unwrap()
+            .to_string();
+
+        let users = Htpasswd::parse(&format!("# users\nalice:{bcrypt}\n\nbob:{argon2}\n")).unwrap();
+
+        assert!(users.verify("alice", "hunter2"));
+        assert!(!users.verify("alice", "swordfish"));
+        assert!(users.verify("bob", "swordfish"));
+        assert!(!users.verify("carol", "hunter2"));
+    }
+
+    #[test]
+    fn rejects_unsupported_hashes() {
+        let err = Htpasswd::parse("alice:{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=").unwrap_err();
+
+        assert!(err.to_string().contains("line 1"));
+    }
+
+    #[test]
+    fn basic_rule_protects_prefix_only() {
+        let users =
+            Htpasswd::parse(&format!("alice:{}", bcrypt::hash("hunter2", 4).unwrap())).unwrap();
+        let auth = Auth::new(vec![Rule::basic("/internal", "staff", users)]);
+
+        assert!(auth.check(&request("/", None)).is_ok());
+        assert!(auth.check(&request("/internalish", None)).is_ok());
+        assert!(auth
+            .check(&request("/internal/x", Some(&basic("alice", "hunter2"))))
+            .is_ok());
+
+        let response = auth
+            .check(&request("/internal", Some(&basic("alice", "wrong"))))
+            .unwrap_err();
+        assert_eq!(401, response.status);
+        assert_eq!(
+            vec![(
+                "WWW-Authenticate".to_string(),
+                "Basic realm=\"staff\", charset=\"UTF-8\"".to_string()
+            )],
+            response.headers
+        );
+    }
+
+    #[test]
+    fn bearer_rule_checks_tokens() {
+        let auth = Auth::new(vec![Rule::bearer(
+            "/api",
+            "api",
+            vec!["s3cret".to_string()],
+        )]);
+
+        assert!(auth.check(&request("/api", Some("Bearer s3cret"))).is_ok());
+
+        let missing = auth.check(&request("/api/x", None)).unwrap_err();
+        assert_eq!("Bearer realm=\"api\"", missing.headers[0].1);
+
+        let wrong = auth
+            .check(&request("/api/x", Some("Bearer nope")))
+            .unwrap_err();
+        assert_eq!(
+            "Bearer realm=\"api\", error=\"invalid_token\"",
+            wrong.headers[0].1
+        );
+    }
+}
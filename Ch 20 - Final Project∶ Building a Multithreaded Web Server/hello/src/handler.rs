@@ -0,0 +1,152 @@
+use crate::http::{Request, Response};
+use crate::template::{Context, Templates};
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+/// Turns a request into a response.
+///
+/// Each virtual host is served by one handler.
+pub trait Handler: Send + Sync {
+    fn handle(&self, request: &Request) -> Response;
+}
+
+/// The hello pages: `/`, `/sleep` and a 404 page for everything else.
+#[derive(Debug, Clone)]
+pub struct App {
+    templates: Templates,
+}
+
+impl App {
+    pub fn new(templates: Templates) -> App {
+        App { templates }
+    }
+}
+
+impl Default for App {
+    fn default() -> App {
+        App::new(Templates::new("templates"))
+    }
+}
+
+impl Handler for App {
+    fn handle(&self, request: &Request) -> Response {
+        let hello = || {
+            let context = Context::new()
+                .insert("title", "Hello!")
+                .insert("message", "Hi from Rust");
+            self.templates.render("hello.html", &context)
+        };
+
+        let rendered = match (request.method.as_str(), request.path.as_str()) {
+            ("GET", "/") => hello().map(Response::ok),
+            ("GET", "/sleep") => {
+                thread::sleep(Duration::from_secs(5));
+                hello().map(Response::ok)
+            }
+            _ => {
+                let context = Context::new()
+                    .insert("title", "Hello!")
+                    .insert("path", request.path.as_str());
+                self.templates
+                    .render("404.html", &context)
+                    .map(Response::not_found)
+            }
+        };
+
+        rendered.unwrap_or_else(|e| {
+            eprintln!("Failed to render page: {e}");
+            Response::internal_server_error()
+        })
+    }
+}
+
+/// Serves files from a document root, with `index.html` for directories.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+}
+
+impl StaticFiles {
+    pub fn new<P: AsRef<Path>>(root: P) -> StaticFiles {
+        StaticFiles {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Map a request path onto the document root, refusing anything that
+    /// would climb out of it.
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        let path = path.split('?').next().unwrap_or_default();
+        let mut resolved = self.root.clone();
+
+        for component in Path::new(path.trim_start_matches('/')).components() {
+            match component {
+                Component::Normal(part) => resolved.push(part),
+                Component::CurDir => {}
+                _ => return None,
+            }
+        }
+
+        if resolved.is_dir() {
+            resolved.push("index.html");
+        }
+
+        Some(resolved)
+    }
+}
+
+impl Handler for StaticFiles {
+    fn handle(&self, request: &Request) -> Response {
+        if request.method != "GET" {
+            return Response::new(405, "METHOD NOT ALLOWED", "").with_header("Allow", "GET");
+        }
+
+        let Some(path) = self.resolve(&request.path) else {
+            return Response::not_found("Not found.\n");
+        };
+
+        match fs::read(&path) {
+            Ok(contents) => Response::ok(contents).with_header("Content-Type", content_type(&path)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Response::not_found("Not found.\n"),
+            Err(e) => {
+                eprintln!("Failed to read {}: {e}", path.display());
+                Response::internal_server_error()
+            }
+        }
+    }
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("html") | Some("htm") => "text/html; charset=utf-8",
+        Some("css") => "text/css",
+        Some("js") => "text/javascript",
+        Some("json") => "application/json",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn static_files_stay_inside_document_root() {
+        let files = StaticFiles::new("templates");
+
+        assert_eq!(
+            Some(PathBuf::from("templates/hello.html")),
+            files.resolve("/hello.html?x=1")
+        );
+        assert_eq!(None, files.resolve("/../Cargo.toml"));
+        assert_eq!(None, files.resolve("/a/../../Cargo.toml"));
+    }
+}
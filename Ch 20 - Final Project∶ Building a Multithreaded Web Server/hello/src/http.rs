@@ -0,0 +1,325 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, prelude::*},
+};
+
+/// The longest request line or header line we read, in bytes, including
+/// the line ending.
+pub const MAX_LINE_LEN: usize = 8 * 1024;
+
+/// The most headers we accept in one request.
+pub const MAX_HEADERS: usize = 100;
+
+/// The parts of an HTTP/1.1 request the server cares about.
+///
+/// `path` is normalised as the request is parsed, so that access rules and
+/// handlers all see the same path for the same resource.
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub version: String,
+    pub headers: Vec<(String, String)>,
+}
+
+impl Request {
+    /// Read a request line and its headers, stopping at the blank line that
+    /// separates them from the body.
+    ///
+    /// Lines longer than `MAX_LINE_LEN` and more than `MAX_HEADERS` headers
+    /// are refused, so a client cannot make us buffer without bound.
+    pub fn parse<R: BufRead>(reader: &mut R) -> Result<Request, ParseError> {
+        let request_line = read_line(reader, ParseError::UriTooLong)?.ok_or(
+            ParseError::Malformed("connection closed before request line"),
+        )?;
+
+        let mut parts = request_line.split_whitespace();
+        let (method, path, version) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(method), Some(path), Some(version)) => (method, path, version),
+            _ => return Err(ParseError::Malformed("malformed request line")),
+        };
+
+        let mut headers = Vec::new();
+        while let Some(line) = read_line(reader, ParseError::HeadersTooLarge)? {
+            if line.is_empty() {
+                break;
+            }
+            if headers.len() == MAX_HEADERS {
+                return Err(ParseError::HeadersTooLarge);
+            }
+
+            match line.split_once(':') {
+                Some((name, value)) => {
+                    headers.push((name.trim().to_string(), value.trim().to_string()))
+                }
+                None => return Err(ParseError::Malformed("malformed header")),
+            }
+        }
+
+        Ok(Request {
+            method: method.to_string(),
+            path: normalize_path(path)?,
+            version: version.to_string(),
+            headers,
+        })
+    }
+
+    /// Look up a header by name, ignoring ASCII case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// Why a request could not be read.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The request line was longer than `MAX_LINE_LEN`.
+    UriTooLong,
+    /// A header line was longer than `MAX_LINE_LEN`, or there were more
+    /// than `MAX_HEADERS` of them.
+    HeadersTooLarge,
+    Malformed(&'static str),
+    Io(io::Error),
+}
+
+impl ParseError {
+    /// The response that tells the client what was wrong, unless the
+    /// connection itself failed.
+    pub fn response(&self) -> Option<Response> {
+        match self {
+            ParseError::UriTooLong => Some(Response::new(
+                414,
+                "URI TOO LONG",
+                "Request line too long.\n",
+            )),
+            ParseError::HeadersTooLarge => Some(Response::new(
+                431,
+                "REQUEST HEADER FIELDS TOO LARGE",
+                "Request headers too large.\n",
+            )),
+            ParseError::Malformed(_) => Some(Response::new(400, "BAD REQUEST", "Bad request.\n")),
+            ParseError::Io(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::UriTooLong => write!(f, "request line too long"),
+            ParseError::HeadersTooLarge => write!(f, "request headers too large"),
+            ParseError::Malformed(message) => write!(f, "{message}"),
+            ParseError::Io(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        ParseError::Io(e)
+    }
+}
+
+impl From<ParseError> for io::Error {
+    fn from(e: ParseError) -> io::Error {
+        match e {
+            ParseError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// A response that is written to the client in one go.
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub reason: &'static str,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn new(status: u16, reason: &'static str, body: impl Into<Vec<u8>>) -> Response {
+        Response {
+            status,
+            reason,
+            headers: Vec::new(),
+            body: body.into(),
+        }
+    }
+
+    pub fn ok(body: impl Into<Vec<u8>>) -> Response {
+        Response::new(200, "OK", body)
+    }
+
+    pub fn not_found(body: impl Into<Vec<u8>>) -> Response {
+        Response::new(404, "NOT FOUND", body)
+    }
+
+    pub fn too_many_requests(retry_after_secs: u64) -> Response {
+        Response::new(429, "TOO MANY REQUESTS", "Too many requests, slow down.\n")
+            .with_header("Retry-After", retry_after_secs.to_string())
+    }
+
+    pub fn internal_server_error() -> Response {
+        Response::new(500, "INTERNAL SERVER ERROR", "Something went wrong.\n")
+    }
+
+    pub fn with_header(mut self, name: &str, value: impl Into<String>) -> Response {
+        self.headers.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        write!(writer, "HTTP/1.1 {} {}\r\n", self.status, self.reason)?;
+        for (name, value) in &self.headers {
+            write!(writer, "{name}: {value}\r\n")?;
+        }
+        write!(writer, "Content-Length: {}\r\n\r\n", self.body.len())?;
+        writer.write_all(&self.body)?;
+        writer.flush()
+    }
+}
+
+/// Read one line of at most `MAX_LINE_LEN` bytes, failing with
+/// `too_long` if it goes on for longer.
+fn read_line<R: BufRead>(
+    reader: &mut R,
+    too_long: ParseError,
+) -> Result<Option<String>, ParseError> {
+    let mut line = Vec::new();
+    let limit = MAX_LINE_LEN as u64 + 1;
+    if reader.take(limit).read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.len() > MAX_LINE_LEN {
+        return Err(too_long);
+    }
+
+    let mut line =
+        String::from_utf8(line).map_err(|_| ParseError::Malformed("request is not UTF-8"))?;
+    let trimmed = line.trim_end_matches(['\r', '\n']).len();
+    line.truncate(trimmed);
+    Ok(Some(line))
+}
+
+/// Collapse repeated slashes and drop `.` segments from the path part of a
+/// request target, keeping any query string as it is. Paths that do not
+/// start at the root, or that climb out of it with `..`, are refused.
+fn normalize_path(target: &str) -> Result<String, ParseError> {
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (target, None),
+    };
+    if !path.starts_with('/') {
+        return Err(ParseError::Malformed("request path is not absolute"));
+    }
+
+    let mut normalized = String::new();
+    for segment in path.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => return Err(ParseError::Malformed("request path leaves the root")),
+            segment => {
+                normalized.push('/');
+                normalized.push_str(segment);
+            }
+        }
+    }
+    if normalized.is_empty() || path.ends_with('/') || path.ends_with("/.") {
+        normalized.push('/');
+    }
+
+    if let Some(query) = query {
+        normalized.push('?');
+        normalized.push_str(query);
+    }
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_request_line_and_headers() {
+        let raw = "GET /sleep HTTP/1.1\r\nHost: localhost:7878\r\nAccept: */*\r\n\r\n";
+        let request = Request::parse(&mut raw.as_bytes()).unwrap();
+
+        assert_eq!("GET", request.method);
+        assert_eq!("/sleep", request.path);
+        assert_eq!("HTTP/1.1", request.version);
+        assert_eq!(Some("localhost:7878"), request.header("host"));
+        assert_eq!(None, request.header("Authorization"));
+    }
+
+    #[test]
+    fn normalizes_the_path() {
+        let path = |target: &str| {
+            let raw = format!("GET {target} HTTP/1.1\r\n\r\n");
+            Request::parse(&mut raw.as_bytes()).map(|request| request.path)
+        };
+
+        assert_eq!("/", path("/").unwrap());
+        assert_eq!("/internal/x", path("//internal//x").unwrap());
+        assert_eq!("/internal/x", path("/./internal/./x").unwrap());
+        assert_eq!("/docs/", path("/docs/.").unwrap());
+        assert_eq!("/a?b=//./", path("/a?b=//./").unwrap());
+        assert!(path("/internal/../x").is_err());
+        assert!(path("internal/x").is_err());
+    }
+
+    #[test]
+    fn rejects_malformed_request_line() {
+        let err = Request::parse(&mut "GET\r\n\r\n".as_bytes()).unwrap_err();
+
+        assert_eq!(400, err.response().unwrap().status);
+    }
+
+    #[test]
+    fn refuses_long_lines_and_too_many_headers() {
+        let long_path = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LEN));
+        let err = Request::parse(&mut long_path.as_bytes()).unwrap_err();
+        assert_eq!(414, err.response().unwrap().status);
+
+        let long_header = format!("GET / HTTP/1.1\r\nX: {}\r\n\r\n", "a".repeat(MAX_LINE_LEN));
+        let err = Request::parse(&mut long_header.as_bytes()).unwrap_err();
+        assert_eq!(431, err.response().unwrap().status);
+
+        let many = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X: 1\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let err = Request::parse(&mut many.as_bytes()).unwrap_err();
+        assert_eq!(431, err.response().unwrap().status);
+
+        let enough = format!("GET / HTTP/1.1\r\n{}\r\n", "X: 1\r\n".repeat(MAX_HEADERS));
+        assert_eq!(
+            MAX_HEADERS,
+            Request::parse(&mut enough.as_bytes())
+                .unwrap()
+                .headers
+                .len()
+        );
+    }
+
+    #[test]
+    fn writes_status_headers_and_body() {
+        let mut out = Vec::new();
+        Response::not_found("nope")
+            .with_header("Content-Type", "text/plain")
+            .write_to(&mut out)
+            .unwrap();
+
+        assert_eq!(
+            "HTTP/1.1 404 NOT FOUND\r\nContent-Type: text/plain\r\nContent-Length: 4\r\n\r\nnope",
+            String::from_utf8(out).unwrap()
+        );
+    }
+}
@@ -3,6 +3,21 @@ use std::{
     thread,
 };
 
+pub mod auth;
+pub mod handler;
+pub mod http;
+pub mod limit;
+pub mod server;
+pub mod template;
+pub mod vhost;
+
+pub use auth::{Auth, Htpasswd, Rule};
+pub use handler::{App, Handler, StaticFiles};
+pub use limit::{Limits, RateLimit};
+pub use server::{Config, Server, ServerHandle};
+pub use template::{Context, Templates};
+pub use vhost::{VirtualHost, VirtualHosts};
+
 pub struct ThreadPool {
     workers: Vec<Worker>,
     sender: Option<mpsc::Sender<Job>>,
@@ -0,0 +1,299 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Token-bucket settings applied to each client IP.
+///
+/// A client may open `burst` connections back to back, after which it gets
+/// `per_second` more every second.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    /// Build a rate limit, checking that it can ever admit anyone.
+    pub fn new(per_second: f64, burst: u32) -> Result<RateLimit, &'static str> {
+        let rate = RateLimit { per_second, burst };
+        rate.check()?;
+        Ok(rate)
+    }
+
+    /// The fields are public, so a rate built by hand is checked again
+    /// before a server uses it.
+    pub fn check(&self) -> Result<(), &'static str> {
+        if !(self.per_second.is_finite() && self.per_second > 0.0) {
+            return Err("rate must be a positive number of connections per second");
+        }
+        if self.burst < 1 {
+            return Err("burst must allow at least one connection");
+        }
+        Ok(())
+    }
+}
+
+/// Connection limits enforced before a connection reaches the thread pool.
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    pub rate: Option<RateLimit>,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_ip: Option<usize>,
+}
+
+/// Limits loose enough for clients that all share one address, as they do
+/// behind a proxy or when everything runs on localhost. A per-IP cap is
+/// opt-in: set `max_connections_per_ip` in the server's `Config`.
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            rate: Some(RateLimit {
+                per_second: 10.0,
+                burst: 20,
+            }),
+            max_connections: Some(64),
+            max_connections_per_ip: None,
+        }
+    }
+}
+
+impl Limits {
+    pub fn unlimited() -> Limits {
+        Limits {
+            rate: None,
+            max_connections: None,
+            max_connections_per_ip: None,
+        }
+    }
+
+    pub fn check(&self) -> Result<(), &'static str> {
+        self.rate.as_ref().map_or(Ok(()), RateLimit::check)
+    }
+}
+
+/// Why a connection was turned away, and when the client may try again.
+#[derive(Debug, PartialEq)]
+pub struct Rejection {
+    pub retry_after: Duration,
+}
+
+impl Rejection {
+    /// The `Retry-After` value in whole seconds, rounded up.
+    pub fn retry_after_secs(&self) -> u64 {
+        let secs = self.retry_after.as_secs();
+        if self.retry_after.subsec_nanos() > 0 {
+            secs + 1
+        } else {
+            secs.max(1)
+        }
+    }
+}
+
+const PRUNE_THRESHOLD: usize = 1024;
+
+pub struct Limiter {
+    limits: Limits,
+    state: Arc<Mutex<State>>,
+}
+
+#[derive(Default)]
+struct State {
+    total: usize,
+    clients: HashMap<IpAddr, Client>,
+}
+
+struct Client {
+    tokens: f64,
+    updated: Instant,
+    active: usize,
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Limiter {
+        Limiter {
+            limits,
+            state: Arc::new(Mutex::new(State::default())),
+        }
+    }
+
+    /// Admit a connection from `ip`, or say how long it should wait.
+    ///
+    /// The returned guard holds the connection's slot until it is dropped.
+    pub fn acquire(&self, ip: IpAddr) -> Result<ConnectionGuard, Rejection> {
+        self.acquire_at(ip, Instant::now())
+    }
+
+    fn acquire_at(&self, ip: IpAddr, now: Instant) -> Result<ConnectionGuard, Rejection> {
+        let mut state = self.state.lock().unwrap();
+
+        if state.clients.len() > PRUNE_THRESHOLD {
+            let rate = self.limits.rate;
+            state
+                .clients
+                .retain(|_, client| client.active > 0 || !client.is_full(rate, now));
+        }
+
+        let busy = Rejection {
+            retry_after: Duration::from_secs(1),
+        };
+
+        if let Some(max) = self.limits.max_connections {
+            if state.total >= max {
+                return Err(busy);
+            }
+        }
+
+        let client = state.clients.entry(ip).or_insert_with(|| Client {
+            tokens: self.limits.rate.map_or(0.0, |rate| f64::from(rate.burst)),
+            updated: now,
+            active: 0,
+        });
+
+        if let Some(max) = self.limits.max_connections_per_ip {
+            if client.active >= max {
+                return Err(busy);
+            }
+        }
+
+        if let Some(rate) = self.limits.rate {
+            client.refill(rate, now);
+
+            if client.tokens < 1.0 {
+                let wait = (1.0 - client.tokens) / rate.per_second;
+                return Err(Rejection {
+                    retry_after: Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX),
+                });
+            }
+
+            client.tokens -= 1.0;
+        }
+
+        client.active += 1;
+        state.total += 1;
+
+        Ok(ConnectionGuard {
+            ip,
+            state: Arc::clone(&self.state),
+        })
+    }
+}
+
+impl Client {
+    fn refill(&mut self, rate: RateLimit, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(f64::from(rate.burst));
+        self.updated = now;
+    }
+
+    fn is_full(&self, rate: Option<RateLimit>, now: Instant) -> bool {
+        match rate {
+            Some(rate) => {
+                let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+                self.tokens + elapsed * rate.per_second >= f64::from(rate.burst)
+            }
+            None => true,
+        }
+    }
+}
+
+/// Releases a connection slot when dropped.
+pub struct ConnectionGuard {
+    ip: IpAddr,
+    state: Arc<Mutex<State>>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        let mut state = self.state.lock().unwrap();
+        state.total -= 1;
+
+        if let Some(client) = state.clients.get_mut(&self.ip) {
+            client.active -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const ALICE: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+    const BOB: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2));
+
+    fn rate_only(per_second: f64, burst: u32) -> Limiter {
+        Limiter::new(Limits {
+            rate: Some(RateLimit { per_second, burst }),
+            ..Limits::unlimited()
+        })
+    }
+
+    #[test]
+    fn bucket_allows_burst_then_refills() {
+        let limiter = rate_only(2.0, 3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(limiter.acquire_at(ALICE, now).is_ok());
+        }
+        let rejection = limiter.acquire_at(ALICE, now).err().unwrap();
+        assert_eq!(Duration::from_millis(500), rejection.retry_after);
+        assert_eq!(1, rejection.retry_after_secs());
+
+        assert!(limiter.acquire_at(BOB, now).is_ok());
+        assert!(limiter
+            .acquire_at(ALICE, now + Duration::from_millis(500))
+            .is_ok());
+    }
+
+    #[test]
+    fn rates_that_never_refill_are_invalid() {
+        assert!(RateLimit::new(0.5, 1).is_ok());
+        assert!(RateLimit::new(0.0, 1).is_err());
+        assert!(RateLimit::new(-1.0, 1).is_err());
+        assert!(RateLimit::new(f64::NAN, 1).is_err());
+        assert!(RateLimit::new(f64::INFINITY, 1).is_err());
+        assert!(RateLimit::new(1.0, 0).is_err());
+    }
+
+    #[test]
+    fn caps_concurrent_connections_per_ip() {
+        let limiter = Limiter::new(Limits {
+            max_connections_per_ip: Some(1),
+            ..Limits::unlimited()
+        });
+
+        let guard = limiter.acquire(ALICE).unwrap();
+        assert!(limiter.acquire(ALICE).is_err());
+        assert!(limiter.acquire(BOB).is_ok());
+
+        drop(guard);
+        assert!(limiter.acquire(ALICE).is_ok());
+    }
+
+    #[test]
+    fn default_limits_let_one_address_use_every_worker() {
+        let limiter = Limiter::new(Limits::default());
+
+        let guards: Vec<_> = (0..8).map(|_| limiter.acquire(ALICE)).collect();
+        assert!(guards.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn caps_concurrent_connections_overall() {
+        let limiter = Limiter::new(Limits {
+            max_connections: Some(2),
+            ..Limits::unlimited()
+        });
+
+        let _alice = limiter.acquire(ALICE).unwrap();
+        let bob = limiter.acquire(BOB).unwrap();
+        assert!(limiter.acquire(ALICE).is_err());
+
+        drop(bob);
+        assert!(limiter.acquire(ALICE).is_ok());
+    }
+}
@@ -0,0 +1,278 @@
+use crate::auth::Auth;
+use crate::http::{Request, Response};
+use crate::limit::{Limiter, Limits, Rejection};
+use crate::vhost::VirtualHosts;
+use crate::ThreadPool;
+use std::{
+    io::{self, prelude::*, BufReader},
+    net::{
+        IpAddr, Ipv4Addr, Ipv6Addr, Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs,
+    },
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long a turned-away client gets to send its request before we give up
+/// on reading it.
+const REJECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How many turned-away connections may wait for a 429 at once. Past this,
+/// connections are closed without an answer rather than queued, since each
+/// one holds a file descriptor for up to `REJECT_TIMEOUT`.
+const MAX_PENDING_REJECTIONS: usize = 32;
+
+/// How much of a bad request we read and throw away after answering it, so
+/// that closing the socket does not reset the connection under the answer.
+const MAX_DRAIN: u64 = 64 * 1024;
+
+/// Settings for a `Server`.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Number of worker threads serving requests.
+    pub workers: usize,
+    /// How long a client may take to send its request, or to read the
+    /// response, before it is disconnected.
+    pub timeout: Duration,
+    pub limits: Limits,
+    pub auth: Auth,
+    pub hosts: VirtualHosts,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            workers: 4,
+            timeout: Duration::from_secs(10),
+            limits: Limits::default(),
+            auth: Auth::default(),
+            hosts: VirtualHosts::default(),
+        }
+    }
+}
+
+/// A bound listener together with the pool that serves its connections.
+pub struct Server {
+    listener: TcpListener,
+    pool: ThreadPool,
+    limiter: Limiter,
+    rejections: ThreadPool,
+    pending_rejections: Arc<AtomicUsize>,
+    site: Arc<Site>,
+}
+
+/// Everything a worker needs to answer a request.
+struct Site {
+    auth: Auth,
+    hosts: VirtualHosts,
+    timeout: Duration,
+}
+
+impl Server {
+    /// Bind the server to `addr` with the default configuration.
+    ///
+    /// Binding to port 0 lets the operating system pick a free port; use
+    /// `local_addr` to find out which one it chose.
+    pub fn bind<A: ToSocketAddrs>(addr: A) -> io::Result<Server> {
+        Server::with_config(addr, Config::default())
+    }
+
+    pub fn with_config<A: ToSocketAddrs>(addr: A, config: Config) -> io::Result<Server> {
+        config
+            .limits
+            .check()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let listener = TcpListener::bind(addr)?;
+
+        Ok(Server {
+            listener,
+            pool: ThreadPool::new(config.workers),
+            limiter: Limiter::new(config.limits),
+            rejections: ThreadPool::new(1),
+            pending_rejections: Arc::new(AtomicUsize::new(0)),
+            site: Arc::new(Site {
+                auth: config.auth,
+                hosts: config.hosts,
+                timeout: config.timeout,
+            }),
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Serve connections on the current thread until the listener fails.
+    pub fn run(self) {
+        self.serve(&AtomicBool::new(false));
+    }
+
+    /// Serve connections on a background thread.
+    ///
+    /// The returned handle stops the server when `shutdown` is called or
+    /// when it is dropped.
+    pub fn spawn(self) -> io::Result<ServerHandle> {
+        let addr = self.local_addr()?;
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let flag = Arc::clone(&shutdown);
+        let thread = thread::spawn(move || self.serve(&flag));
+
+        Ok(ServerHandle {
+            addr,
+            shutdown,
+            thread: Some(thread),
+        })
+    }
+
+    fn serve(self, shutdown: &AtomicBool) {
+        for stream in self.listener.incoming() {
+            if shutdown.load(Ordering::SeqCst) {
+                break;
+            }
+
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept connection: {e}");
+                    continue;
+                }
+            };
+
+            let guard = match stream.peer_addr() {
+                Ok(peer) => self.limiter.acquire(peer.ip()),
+                Err(e) => {
+                    eprintln!("Failed to read peer address: {e}");
+                    continue;
+                }
+            };
+
+            match guard {
+                Ok(guard) => {
+                    let site = Arc::clone(&self.site);
+                    self.pool.execute(move || {
+                        if let Err(e) = handle_connection(stream, &site) {
+                            eprintln!("Failed to handle connection: {e}");
+                        }
+                        drop(guard);
+                    })
+                }
+                // Rejections get their own worker so that a flood of them
+                // cannot tie up the pool that serves admitted clients, and
+                // past a point they are not answered at all.
+                Err(rejection) => {
+                    let pending = Arc::clone(&self.pending_rejections);
+                    if pending.fetch_add(1, Ordering::SeqCst) >= MAX_PENDING_REJECTIONS {
+                        pending.fetch_sub(1, Ordering::SeqCst);
+                        continue;
+                    }
+
+                    self.rejections.execute(move || {
+                        if let Err(e) = reject_connection(stream, rejection) {
+                            eprintln!("Failed to reject connection: {e}");
+                        }
+                        pending.fetch_sub(1, Ordering::SeqCst);
+                    })
+                }
+            }
+        }
+
+        println!("Shutting down!");
+    }
+}
+
+/// Controls a server started with `Server::spawn`.
+pub struct ServerHandle {
+    addr: SocketAddr,
+    shutdown: Arc<AtomicBool>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+
+impl ServerHandle {
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Stop accepting connections and wait for in-flight requests to finish.
+    pub fn shutdown(mut self) {
+        self.stop();
+    }
+
+    fn stop(&mut self) {
+        let Some(thread) = self.thread.take() else {
+            return;
+        };
+
+        self.shutdown.store(true, Ordering::SeqCst);
+
+        // The accept loop is blocked in `incoming`, so poke it with a
+        // connection of our own to make it notice the flag.
+        let _ = TcpStream::connect(wake_addr(self.addr));
+
+        if thread.join().is_err() {
+            eprintln!("Server thread panicked");
+        }
+    }
+}
+
+impl Drop for ServerHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn wake_addr(addr: SocketAddr) -> SocketAddr {
+    match addr.ip() {
+        IpAddr::V4(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), addr.port())
+        }
+        IpAddr::V6(ip) if ip.is_unspecified() => {
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::LOCALHOST), addr.port())
+        }
+        _ => addr,
+    }
+}
+
+fn handle_connection(mut stream: TcpStream, site: &Site) -> io::Result<()> {
+    // Without timeouts, a client that goes quiet would hold a worker
+    // forever.
+    stream.set_read_timeout(Some(site.timeout))?;
+    stream.set_write_timeout(Some(site.timeout))?;
+
+    let request = match Request::parse(&mut BufReader::new(&mut stream)) {
+        Ok(request) => request,
+        Err(e) => {
+            if let Some(response) = e.response() {
+                response.write_to(&mut stream)?;
+                drain(&mut stream);
+            }
+            return Err(e.into());
+        }
+    };
+    let response = match site.auth.check(&request) {
+        Ok(()) => site.hosts.handle(&request),
+        Err(challenge) => challenge,
+    };
+
+    response.write_to(&mut stream)
+}
+
+/// Stop writing and read what is left of the request, up to a limit, so
+/// that the client gets to read our answer before the connection closes.
+fn drain(stream: &mut TcpStream) {
+    let _ = stream.shutdown(Shutdown::Write);
+    let _ = io::copy(&mut stream.take(MAX_DRAIN), &mut io::sink());
+}
+
+fn reject_connection(mut stream: TcpStream, rejection: Rejection) -> io::Result<()> {
+    // Read the request before answering so that closing the socket does not
+    // reset the connection while the client still has data in flight.
+    stream.set_read_timeout(Some(REJECT_TIMEOUT))?;
+    stream.set_write_timeout(Some(REJECT_TIMEOUT))?;
+    Request::parse(&mut BufReader::new(&mut stream))?;
+
+    Response::too_many_requests(rejection.retry_after_secs()).write_to(&mut stream)
+}
@@ -0,0 +1,505 @@
+//! A very small HTML template language.
+//!
+//! - `{{ name }}` inserts a value, HTML-escaped. Dotted paths such as
+//!   `{{ user.name }}` look inside maps.
+//! - `{{ name | raw }}` inserts a value without escaping.
+//! - `{% for item in items %}...{% endfor %}` repeats its body for each
+//!   element of a list.
+//! - `{% include "head.html" %}` renders another template with the same
+//!   data.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fmt, fs,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+/// How deep includes may nest before we assume a cycle.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Str(String),
+    List(Vec<Value>),
+    Map(HashMap<String, Value>),
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::Str(s)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Value {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Context> for Value {
+    fn from(context: Context) -> Value {
+        Value::Map(context.values)
+    }
+}
+
+/// The data a template is rendered with.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Context {
+    values: HashMap<String, Value>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context::default()
+    }
+
+    pub fn insert(mut self, name: &str, value: impl Into<Value>) -> Context {
+        self.values.insert(name.to_string(), value.into());
+        self
+    }
+
+    fn lookup(&self, path: &str) -> Option<&Value> {
+        let (name, rest) = split_path(path);
+        descend(self.values.get(name)?, rest)
+    }
+}
+
+/// The first name of a dotted path, and the rest of it.
+fn split_path(path: &str) -> (&str, Option<&str>) {
+    match path.split_once('.') {
+        Some((name, rest)) => (name, Some(rest)),
+        None => (path, None),
+    }
+}
+
+/// Follow the rest of a dotted path into the maps inside `value`.
+fn descend<'a>(mut value: &'a Value, rest: Option<&str>) -> Option<&'a Value> {
+    for part in rest.into_iter().flat_map(|rest| rest.split('.')) {
+        match value {
+            Value::Map(map) => value = map.get(part)?,
+            _ => return None,
+        }
+    }
+
+    Some(value)
+}
+
+/// The names a node can see: the context it is rendered with, and the
+/// item of each loop it is in. Each loop adds a link to the chain rather
+/// than copying the context, list and all, for every item.
+enum Scope<'a> {
+    Root(&'a Context),
+    Loop {
+        item: &'a str,
+        value: &'a Value,
+        parent: &'a Scope<'a>,
+    },
+}
+
+impl Scope<'_> {
+    fn lookup(&self, path: &str) -> Option<&Value> {
+        match self {
+            Scope::Root(context) => context.lookup(path),
+            Scope::Loop {
+                item,
+                value,
+                parent,
+            } => match split_path(path) {
+                (name, rest) if name == *item => descend(value, rest),
+                _ => parent.lookup(path),
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TemplateError {
+    pub template: String,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "template {}: {}", self.template, self.message)
+    }
+}
+
+impl Error for TemplateError {}
+
+/// Loads templates from a directory and renders them.
+///
+/// Parsed templates are kept and reused until their file changes.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    dir: PathBuf,
+    cache: Arc<Mutex<HashMap<String, Parsed>>>,
+}
+
+#[derive(Debug)]
+struct Parsed {
+    modified: SystemTime,
+    nodes: Arc<Vec<Node>>,
+}
+
+impl Templates {
+    pub fn new<P: AsRef<Path>>(dir: P) -> Templates {
+        Templates {
+            dir: dir.as_ref().to_path_buf(),
+            cache: Arc::default(),
+        }
+    }
+
+    /// Parse the template called `name`, or reuse the last parse if the
+    /// file has not been modified since.
+    fn load(&self, name: &str) -> Result<Arc<Vec<Node>>, String> {
+        let path = self.dir.join(name);
+        let modified = fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|e| e.to_string())?;
+
+        if let Some(parsed) = self.cache.lock().unwrap().get(name) {
+            if parsed.modified == modified {
+                return Ok(Arc::clone(&parsed.nodes));
+            }
+        }
+
+        let source = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        let nodes = Arc::new(parse(&source)?);
+        self.cache.lock().unwrap().insert(
+            name.to_string(),
+            Parsed {
+                modified,
+                nodes: Arc::clone(&nodes),
+            },
+        );
+        Ok(nodes)
+    }
+
+    pub fn render(&self, name: &str, context: &Context) -> Result<String, TemplateError> {
+        let mut out = String::new();
+        self.render_into(name, &Scope::Root(context), 0, &mut out)?;
+        Ok(out)
+    }
+
+    fn render_into(
+        &self,
+        name: &str,
+        scope: &Scope,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<(), TemplateError> {
+        let error = |message: String| TemplateError {
+            template: name.to_string(),
+            message,
+        };
+
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(error("includes nest too deeply".to_string()));
+        }
+        // Only plain relative names, so that a template cannot reach outside
+        // the directory with `..`, an absolute path or a drive prefix.
+        let relative = Path::new(name)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)));
+        if !relative || name.split(['/', '\\']).any(|part| part == "..") {
+            return Err(error(
+                "template names must be relative and may not contain '..'".to_string(),
+            ));
+        }
+
+        let nodes = self.load(name).map_err(error)?;
+
+        self.render_nodes(&nodes, scope, depth, out)
+            .map_err(|e| match e {
+                Failure::Here(message) => error(message),
+                Failure::Include(e) => e,
+            })
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        scope: &Scope,
+        depth: usize,
+        out: &mut String,
+    ) -> Result<(), Failure> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Var { path, raw } => match scope.lookup(path) {
+                    Some(Value::Str(s)) if *raw => out.push_str(s),
+                    Some(Value::Str(s)) => escape_into(s, out),
+                    Some(_) => return Err(Failure::Here(format!("`{path}` is not a string"))),
+                    None => return Err(Failure::Here(format!("`{path}` is not defined"))),
+                },
+                Node::For { item, list, body } => match scope.lookup(list) {
+                    Some(Value::List(values)) => {
+                        for value in values {
+                            let inner = Scope::Loop {
+                                item,
+                                value,
+                                parent: scope,
+                            };
+                            self.render_nodes(body, &inner, depth, out)?;
+                        }
+                    }
+                    Some(_) => return Err(Failure::Here(format!("`{list}` is not a list"))),
+                    None => return Err(Failure::Here(format!("`{list}` is not defined"))),
+                },
+                Node::Include(name) => self
+                    .render_into(name, scope, depth + 1, out)
+                    .map_err(Failure::Include)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Distinguishes errors in the template being rendered from errors that
+/// already carry the name of an included template.
+enum Failure {
+    Here(String),
+    Include(TemplateError),
+}
+
+#[derive(Debug, PartialEq)]
+enum Node {
+    Text(String),
+    Var {
+        path: String,
+        raw: bool,
+    },
+    For {
+        item: String,
+        list: String,
+        body: Vec<Node>,
+    },
+    Include(String),
+}
+
+fn parse(source: &str) -> Result<Vec<Node>, String> {
+    let mut rest = source;
+    let mut stack: Vec<(String, String, Vec<Node>)> = Vec::new();
+    let mut nodes = Vec::new();
+
+    while !rest.is_empty() {
+        let start = match (rest.find("{{"), rest.find("{%")) {
+            (Some(a), Some(b)) => a.min(b),
+            (Some(a), None) | (None, Some(a)) => a,
+            (None, None) => rest.len(),
+        };
+
+        if start > 0 {
+            nodes.push(Node::Text(rest[..start].to_string()));
+            rest = &rest[start..];
+            continue;
+        }
+
+        let close = if rest.starts_with("{{") { "}}" } else { "%}" };
+        let end = rest
+            .find(close)
+            .ok_or_else(|| format!("unclosed `{}`", &rest[..2]))?;
+        let tag = rest[2..end].trim();
+        let is_var = rest.starts_with("{{");
+        rest = &rest[end + 2..];
+
+        if is_var {
+            let (path, raw) = match tag.split_once('|') {
+                Some((path, filter)) if filter.trim() == "raw" => (path.trim(), true),
+                Some((_, filter)) => return Err(format!("unknown filter `{}`", filter.trim())),
+                None => (tag, false),
+            };
+            nodes.push(Node::Var {
+                path: path.to_string(),
+                raw,
+            });
+            continue;
+        }
+
+        let words: Vec<&str> = tag.split_whitespace().collect();
+        match words.as_slice() {
+            ["for", item, "in", list] => {
+                stack.push((item.to_string(), list.to_string(), nodes));
+                nodes = Vec::new();
+            }
+            ["endfor"] => {
+                let (item, list, mut outer) = stack.pop().ok_or("`endfor` without `for`")?;
+                outer.push(Node::For {
+                    item,
+                    list,
+                    body: nodes,
+                });
+                nodes = outer;
+            }
+            ["include", name] if name.len() > 1 && name.starts_with('"') && name.ends_with('"') => {
+                nodes.push(Node::Include(name[1..name.len() - 1].to_string()));
+            }
+            _ => return Err(format!("unknown tag `{tag}`")),
+        }
+    }
+
+    if let Some((item, list, _)) = stack.last() {
+        return Err(format!("`for {item} in {list}` is missing `endfor`"));
+    }
+
+    Ok(nodes)
+}
+
+/// Append `s` to `out` with the characters that are special in HTML replaced
+/// by entities.
+pub fn escape_into(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#x27;"),
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// The directory is removed when the returned `TempDir` is dropped.
+    fn templates(files: &[(&str, &str)]) -> (TempDir, Templates) {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, contents) in files {
+            fs::write(dir.path().join(name), contents).unwrap();
+        }
+        let templates = Templates::new(dir.path());
+        (dir, templates)
+    }
+
+    #[test]
+    fn interpolates_and_escapes() {
+        let (_dir, templates) = templates(&[("escape.html", "<p>{{ path }} {{ html|raw }}</p>")]);
+        let context = Context::new()
+            .insert("path", "/<script>alert('hi')</script>")
+            .insert("html", "<b>bold</b>");
+
+        assert_eq!(
+            "<p>/&lt;script&gt;alert(&#x27;hi&#x27;)&lt;/script&gt; <b>bold</b></p>",
+            templates.render("escape.html", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn loops_over_lists_of_maps() {
+        let (_dir, templates) = templates(&[(
+            "loop.html",
+            "{% for user in users %}<li>{{ user.name }}</li>{% endfor %}",
+        )]);
+        let context = Context::new().insert(
+            "users",
+            vec![
+                Context::new().insert("name", "Ferris"),
+                Context::new().insert("name", "A & B"),
+            ],
+        );
+
+        assert_eq!(
+            "<li>Ferris</li><li>A &amp; B</li>",
+            templates.render("loop.html", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn nested_loops_see_outer_items_and_includes_see_both() {
+        let (_dir, templates) = templates(&[
+            (
+                "nested.html",
+                "{% for row in rows %}{% for cell in row.cells %}\
+                 {% include \"cell.html\" %}{% endfor %};{% endfor %}",
+            ),
+            ("cell.html", "{{ row.name }}{{ cell }}{{ sep }}"),
+        ]);
+        let context = Context::new().insert("sep", ",").insert(
+            "rows",
+            vec![
+                Context::new()
+                    .insert("name", "a")
+                    .insert("cells", vec!["1", "2"]),
+                Context::new()
+                    .insert("name", "b")
+                    .insert("cells", vec!["3"]),
+            ],
+        );
+
+        assert_eq!(
+            "a1,a2,;b3,;",
+            templates.render("nested.html", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn includes_other_templates() {
+        let (_dir, templates) = templates(&[
+            ("include.html", "{% include \"title.html\" %}<p>body</p>"),
+            ("title.html", "<h1>{{ title }}</h1>"),
+        ]);
+        let context = Context::new().insert("title", "Hi");
+
+        assert_eq!(
+            "<h1>Hi</h1><p>body</p>",
+            templates.render("include.html", &context).unwrap()
+        );
+    }
+
+    #[test]
+    fn reports_errors_with_template_name() {
+        let (_dir, templates) = templates(&[
+            ("broken.html", "{% include \"missing.html\" %}"),
+            ("unclosed.html", "{% for x in xs %}"),
+            ("self.html", "{% include \"self.html\" %}"),
+        ]);
+        let context = Context::new();
+
+        let err = templates.render("broken.html", &context).unwrap_err();
+        assert_eq!("missing.html", err.template);
+
+        let err = templates.render("unclosed.html", &context).unwrap_err();
+        assert_eq!("`for x in xs` is missing `endfor`", err.message);
+
+        let err = templates.render("self.html", &context).unwrap_err();
+        assert_eq!("includes nest too deeply", err.message);
+
+        assert!(templates.render("../etc/passwd", &context).is_err());
+        assert!(templates.render("/etc/passwd", &context).is_err());
+        assert!(templates.render("./broken.html", &context).is_err());
+    }
+
+    #[test]
+    fn reparses_templates_that_change() {
+        let (dir, templates) = templates(&[("page.html", "one")]);
+        let context = Context::new();
+        assert_eq!("one", templates.render("page.html", &context).unwrap());
+        assert_eq!("one", templates.render("page.html", &context).unwrap());
+
+        let path = dir.path().join("page.html");
+        fs::write(&path, "two").unwrap();
+        let later = fs::metadata(&path).unwrap().modified().unwrap() + Duration::from_secs(1);
+        fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        assert_eq!("two", templates.render("page.html", &context).unwrap());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::handler::{App, Handler};
+use crate::http::{Request, Response};
+use std::{fmt, sync::Arc};
+
+/// A set of hostnames served by one handler.
+///
+/// A name starting with `*.` matches any subdomain of the rest, so
+/// `*.example.com` matches `www.example.com` but not `example.com`.
+#[derive(Clone)]
+pub struct VirtualHost {
+    names: Vec<String>,
+    handler: Arc<dyn Handler>,
+}
+
+impl VirtualHost {
+    pub fn new<H: Handler + 'static>(names: &[&str], handler: H) -> VirtualHost {
+        VirtualHost {
+            names: names.iter().map(|name| name.to_ascii_lowercase()).collect(),
+            handler: Arc::new(handler),
+        }
+    }
+
+    fn serves(&self, host: &str) -> bool {
+        self.names.iter().any(|name| match name.strip_prefix("*.") {
+            Some(domain) => host
+                .strip_suffix(domain)
+                .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')),
+            None => name == host,
+        })
+    }
+}
+
+impl fmt::Debug for VirtualHost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VirtualHost")
+            .field("names", &self.names)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Picks a handler for each request from its `Host` header.
+///
+/// Requests for hosts that match no virtual host go to the default handler,
+/// unless strict mode is on, in which case they get 421 Misdirected Request.
+#[derive(Debug, Clone)]
+pub struct VirtualHosts {
+    hosts: Vec<VirtualHost>,
+    default: VirtualHost,
+    strict: bool,
+}
+
+impl VirtualHosts {
+    pub fn new<H: Handler + 'static>(default: H) -> VirtualHosts {
+        VirtualHosts {
+            hosts: Vec::new(),
+            default: VirtualHost::new(&[], default),
+            strict: false,
+        }
+    }
+
+    pub fn host(mut self, host: VirtualHost) -> VirtualHosts {
+        self.hosts.push(host);
+        self
+    }
+
+    pub fn strict(mut self, strict: bool) -> VirtualHosts {
+        self.strict = strict;
+        self
+    }
+
+    pub fn handle(&self, request: &Request) -> Response {
+        let host = request.header("Host").map(hostname);
+        let matched = host.and_then(|host| self.hosts.iter().find(|vhost| vhost.serves(&host)));
+
+        match matched {
+            Some(vhost) => vhost.handler.handle(request),
+            None if self.strict => Response::new(
+                421,
+                "MISDIRECTED REQUEST",
+                "This server is not configured for that host.\n",
+            ),
+            None => self.default.handler.handle(request),
+        }
+    }
+}
+
+impl Default for VirtualHosts {
+    fn default() -> VirtualHosts {
+        VirtualHosts::new(App::default())
+    }
+}
+
+/// Lowercase a `Host` header value and drop any port.
+fn hostname(value: &str) -> String {
+    let value = value.trim();
+    let host = if value.starts_with('[') {
+        // An IPv6 literal such as `[::1]:7878`.
+        match value.find(']') {
+            Some(end) => &value[..=end],
+            None => value,
+        }
+    } else {
+        value.rsplit_once(':').map_or(value, |(host, _)| host)
+    };
+
+    host.trim_end_matches('.').to_ascii_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Named(&'static str);
+
+    impl Handler for Named {
+        fn handle(&self, _request: &Request) -> Response {
+            Response::ok(self.0)
+        }
+    }
+
+    fn request(host: Option<&str>) -> Request {
+        Request {
+            method: "GET".to_string(),
+            path: "/".to_string(),
+            version: "HTTP/1.1".to_string(),
+            headers: host
+                .map(|host| vec![("Host".to_string(), host.to_string())])
+                .unwrap_or_default(),
+        }
+    }
+
+    fn body(hosts: &VirtualHosts, host: Option<&str>) -> String {
+        String::from_utf8(hosts.handle(&request(host)).body).unwrap()
+    }
+
+    #[test]
+    fn normalizes_host_header() {
+        assert_eq!("example.com", hostname("Example.COM:8080"));
+        assert_eq!("example.com", hostname("example.com."));
+        assert_eq!("[::1]", hostname("[::1]:7878"));
+    }
+
+    #[test]
+    fn routes_by_host_with_default_fallback() {
+        let hosts = VirtualHosts::new(Named("default"))
+            .host(VirtualHost::new(&["a.test", "www.a.test"], Named("a")))
+            .host(VirtualHost::new(&["*.b.test"], Named("b")));
+
+        assert_eq!("a", body(&hosts, Some("WWW.A.test:7878")));
+        assert_eq!("b", body(&hosts, Some("api.b.test")));
+        assert_eq!("default", body(&hosts, Some("b.test")));
+        assert_eq!("default", body(&hosts, None));
+    }
+
+    #[test]
+    fn strict_mode_rejects_unknown_hosts() {
+        let hosts = VirtualHosts::new(Named("default"))
+            .host(VirtualHost::new(&["a.test"], Named("a")))
+            .strict(true);
+
+        assert_eq!("a", body(&hosts, Some("a.test")));
+        assert_eq!(421, hosts.handle(&request(Some("c.test"))).status);
+        assert_eq!(421, hosts.handle(&request(None)).status);
+    }
+}
@@ -0,0 +1,85 @@
+use hello::{Auth, Config, Htpasswd, Rule, StaticFiles, VirtualHosts};
+use std::{fs, io::Write};
+use tempfile::NamedTempFile;
+
+mod common;
+
+#[test]
+fn bearer_token_protects_prefix() {
+    let server = common::start_with(Config {
+        auth: Auth::new(vec![Rule::bearer("/", "hello", vec!["s3cret".to_string()])]),
+        ..Config::default()
+    });
+    let addr = server.local_addr();
+
+    let (status_line, headers, _) = common::get_with_headers(addr, "/");
+    assert_eq!("HTTP/1.1 401 UNAUTHORIZED", status_line);
+    assert!(headers.contains(&"WWW-Authenticate: Bearer realm=\"hello\"".to_string()));
+
+    let (status_line, _, body) = common::request(
+        addr,
+        "GET / HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer s3cret\r\n\r\n",
+    );
+    assert_eq!("HTTP/1.1 200 OK", status_line);
+    assert!(body.contains("Hi from Rust"));
+}
+
+#[test]
+fn basic_auth_checks_htpasswd_file() {
+    let mut file = NamedTempFile::new().unwrap();
+    let hash = bcrypt::hash("hunter2", 4).unwrap();
+    writeln!(file, "alice:{hash}").unwrap();
+    let users = Htpasswd::load(file.path()).unwrap();
+
+    let server = common::start_with(Config {
+        auth: Auth::new(vec![Rule::basic("/sleep", "staff", users)]),
+        ..Config::default()
+    });
+    let addr = server.local_addr();
+
+    assert_eq!("HTTP/1.1 200 OK", common::get(addr, "/").0);
+
+    let (status_line, headers, _) = common::request(
+        addr,
+        "GET /sleep HTTP/1.1\r\nAuthorization: Basic YWxpY2U6d3Jvbmc=\r\n\r\n",
+    );
+    assert_eq!("HTTP/1.1 401 UNAUTHORIZED", status_line);
+    assert!(
+        headers.contains(&"WWW-Authenticate: Basic realm=\"staff\", charset=\"UTF-8\"".to_string())
+    );
+}
+
+#[test]
+fn unusual_spellings_of_a_protected_path_are_still_protected() {
+    let root = tempfile::tempdir().unwrap();
+    fs::create_dir(root.path().join("internal")).unwrap();
+    fs::write(root.path().join("internal/x"), "secret\n").unwrap();
+
+    let server = common::start_with(Config {
+        auth: Auth::new(vec![Rule::bearer(
+            "/internal",
+            "staff",
+            vec!["s3cret".to_string()],
+        )]),
+        hosts: VirtualHosts::new(StaticFiles::new(root.path())),
+        ..Config::default()
+    });
+    let addr = server.local_addr();
+
+    for path in ["/internal/x", "//internal/x", "/./internal/x"] {
+        let (status_line, _, body) = common::get_with_headers(addr, path);
+        assert_eq!("HTTP/1.1 401 UNAUTHORIZED", status_line, "{path}");
+        assert!(!body.contains("secret"));
+    }
+    assert_eq!(
+        "HTTP/1.1 400 BAD REQUEST",
+        common::get(addr, "/public/../internal/x").0
+    );
+
+    let (status_line, _, body) = common::request(
+        addr,
+        "GET //internal/x HTTP/1.1\r\nAuthorization: Bearer s3cret\r\n\r\n",
+    );
+    assert_eq!("HTTP/1.1 200 OK", status_line);
+    assert_eq!("secret\n", body);
+}
@@ -0,0 +1,49 @@
+// Each test binary uses a different subset of these helpers.
+#![allow(dead_code)]
+
+use hello::{Config, Server, ServerHandle};
+use std::{
+    io::{prelude::*, BufReader},
+    net::{SocketAddr, TcpStream},
+};
+
+pub fn start() -> ServerHandle {
+    Server::bind("127.0.0.1:0").unwrap().spawn().unwrap()
+}
+
+pub fn start_with(config: Config) -> ServerHandle {
+    Server::with_config("127.0.0.1:0", config)
+        .unwrap()
+        .spawn()
+        .unwrap()
+}
+
+/// Send a bare GET request and return the status line and body.
+pub fn get(addr: SocketAddr, path: &str) -> (String, String) {
+    let (status_line, _, body) = get_with_headers(addr, path);
+    (status_line, body)
+}
+
+pub fn get_with_headers(addr: SocketAddr, path: &str) -> (String, Vec<String>, String) {
+    request(
+        addr,
+        &format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+    )
+}
+
+/// Send `raw` as-is and return the status line, header lines and body.
+pub fn request(addr: SocketAddr, raw: &str) -> (String, Vec<String>, String) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    stream.write_all(raw.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    BufReader::new(stream)
+        .read_to_string(&mut response)
+        .unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let mut lines = head.lines().map(str::to_string);
+    let status_line = lines.next().unwrap();
+
+    (status_line, lines.collect(), body.to_string())
+}
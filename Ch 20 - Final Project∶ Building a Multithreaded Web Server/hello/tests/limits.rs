@@ -0,0 +1,65 @@
+use hello::{Config, Limits, RateLimit, Server};
+use std::{io, thread, time::Duration};
+
+mod common;
+
+#[test]
+fn rate_limited_client_gets_429_with_retry_after() {
+    let server = common::start_with(Config {
+        limits: Limits {
+            rate: Some(RateLimit {
+                per_second: 0.1,
+                burst: 2,
+            }),
+            ..Limits::unlimited()
+        },
+        ..Config::default()
+    });
+    let addr = server.local_addr();
+
+    assert_eq!("HTTP/1.1 200 OK", common::get(addr, "/").0);
+    assert_eq!("HTTP/1.1 200 OK", common::get(addr, "/").0);
+
+    let (status_line, headers, _) = common::get_with_headers(addr, "/");
+    assert_eq!("HTTP/1.1 429 TOO MANY REQUESTS", status_line);
+    assert!(headers.contains(&"Retry-After: 10".to_string()));
+}
+
+#[test]
+fn invalid_rate_is_refused_at_startup() {
+    let config = Config {
+        limits: Limits {
+            rate: Some(RateLimit {
+                per_second: 0.0,
+                burst: 1,
+            }),
+            ..Limits::unlimited()
+        },
+        ..Config::default()
+    };
+
+    let err = Server::with_config("127.0.0.1:0", config).err().unwrap();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[test]
+fn per_ip_connection_cap_rejects_extra_connections() {
+    let server = common::start_with(Config {
+        limits: Limits {
+            max_connections_per_ip: Some(1),
+            ..Limits::unlimited()
+        },
+        ..Config::default()
+    });
+    let addr = server.local_addr();
+
+    let sleeper = thread::spawn(move || common::get(addr, "/sleep"));
+    thread::sleep(Duration::from_millis(200));
+
+    let (status_line, headers, _) = common::get_with_headers(addr, "/");
+    assert_eq!("HTTP/1.1 429 TOO MANY REQUESTS", status_line);
+    assert!(headers.contains(&"Retry-After: 1".to_string()));
+
+    assert_eq!("HTTP/1.1 200 OK", sleeper.join().unwrap().0);
+    assert_eq!("HTTP/1.1 200 OK", common::get(addr, "/").0);
+}
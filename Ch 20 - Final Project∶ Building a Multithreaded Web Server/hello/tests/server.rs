@@ -0,0 +1,131 @@
+use hello::http::{MAX_HEADERS, MAX_LINE_LEN};
+use hello::Config;
+use std::{
+    io::Read,
+    net::TcpStream,
+    thread,
+    time::{Duration, Instant},
+};
+
+mod common;
+
+#[test]
+fn serves_hello_page() {
+    let server = common::start();
+
+    let (status_line, body) = common::get(server.local_addr(), "/");
+
+    assert_eq!("HTTP/1.1 200 OK", status_line);
+    assert!(body.contains("Hi from Rust"));
+}
+
+#[test]
+fn unknown_path_is_not_found() {
+    let server = common::start();
+
+    let (status_line, body) = common::get(server.local_addr(), "/nope");
+
+    assert_eq!("HTTP/1.1 404 NOT FOUND", status_line);
+    assert!(body.contains("Oops!"));
+    assert!(body.contains("<code>/nope</code>"));
+}
+
+#[test]
+fn not_found_page_escapes_requested_path() {
+    let server = common::start();
+
+    let (status_line, body) = common::get(server.local_addr(), "/<script>alert(1)</script>");
+
+    assert_eq!("HTTP/1.1 404 NOT FOUND", status_line);
+    assert!(body.contains("<code>/&lt;script&gt;alert(1)&lt;/script&gt;</code>"));
+    assert!(!body.contains("<script>"));
+}
+
+#[test]
+fn binds_an_ephemeral_port() {
+    let first = common::start();
+    let second = common::start();
+
+    assert_ne!(0, first.local_addr().port());
+    assert_ne!(first.local_addr(), second.local_addr());
+}
+
+#[test]
+fn slow_request_does_not_block_others() {
+    let server = common::start();
+    let addr = server.local_addr();
+
+    let sleeper = thread::spawn(move || common::get(addr, "/sleep"));
+    thread::sleep(Duration::from_millis(200));
+
+    let start = Instant::now();
+    let (status_line, _) = common::get(addr, "/");
+
+    assert_eq!("HTTP/1.1 200 OK", status_line);
+    assert!(start.elapsed() < Duration::from_secs(2));
+    assert_eq!("HTTP/1.1 200 OK", sleeper.join().unwrap().0);
+}
+
+#[test]
+fn shutdown_stops_accepting_connections() {
+    let server = common::start();
+    let addr = server.local_addr();
+
+    server.shutdown();
+
+    assert!(TcpStream::connect(addr).is_err());
+}
+
+#[test]
+fn shutdown_waits_for_in_flight_requests() {
+    let server = common::start();
+    let addr = server.local_addr();
+
+    let sleeper = thread::spawn(move || common::get(addr, "/sleep"));
+    thread::sleep(Duration::from_millis(200));
+
+    server.shutdown();
+
+    let (status_line, body) = sleeper.join().unwrap();
+    assert_eq!("HTTP/1.1 200 OK", status_line);
+    assert!(body.contains("Hi from Rust"));
+}
+
+#[test]
+fn oversized_requests_are_refused() {
+    let server = common::start();
+    let addr = server.local_addr();
+
+    let long_path = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE_LEN));
+    assert_eq!(
+        "HTTP/1.1 414 URI TOO LONG",
+        common::request(addr, &long_path).0
+    );
+
+    let many_headers = format!(
+        "GET / HTTP/1.1\r\n{}\r\n",
+        "X-Padding: 1\r\n".repeat(MAX_HEADERS + 1)
+    );
+    assert_eq!(
+        "HTTP/1.1 431 REQUEST HEADER FIELDS TOO LARGE",
+        common::request(addr, &many_headers).0
+    );
+}
+
+#[test]
+fn silent_clients_are_disconnected() {
+    let server = common::start_with(Config {
+        workers: 1,
+        timeout: Duration::from_millis(200),
+        ..Config::default()
+    });
+    let addr = server.local_addr();
+
+    let mut silent = TcpStream::connect(addr).unwrap();
+    let mut response = Vec::new();
+    silent.read_to_end(&mut response).unwrap();
+    assert!(response.is_empty());
+
+    // The only worker is free again.
+    assert_eq!("HTTP/1.1 200 OK", common::get(addr, "/").0);
+}
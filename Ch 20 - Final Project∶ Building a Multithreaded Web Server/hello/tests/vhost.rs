@@ -0,0 +1,47 @@
+use hello::{App, Config, StaticFiles, Templates, VirtualHost, VirtualHosts};
+
+mod common;
+
+fn get_host(server: &hello::ServerHandle, host: &str, path: &str) -> (String, String) {
+    let (status_line, _, body) = common::request(
+        server.local_addr(),
+        &format!("GET {path} HTTP/1.1\r\nHost: {host}\r\n\r\n"),
+    );
+    (status_line, body)
+}
+
+#[test]
+fn routes_hosts_to_their_own_handlers() {
+    let server = common::start_with(Config {
+        hosts: VirtualHosts::new(App::default())
+            .host(VirtualHost::new(&["files.test"], StaticFiles::new("src"))),
+        ..Config::default()
+    });
+
+    let (status_line, body) = get_host(&server, "files.test:80", "/lib.rs");
+    assert_eq!("HTTP/1.1 200 OK", status_line);
+    assert!(body.contains("pub struct ThreadPool"));
+
+    let (status_line, body) = get_host(&server, "other.test", "/");
+    assert_eq!("HTTP/1.1 200 OK", status_line);
+    assert!(body.contains("Hi from Rust"));
+}
+
+#[test]
+fn strict_mode_answers_unknown_hosts_with_421() {
+    let server = common::start_with(Config {
+        hosts: VirtualHosts::new(App::default())
+            .host(VirtualHost::new(
+                &["hello.test"],
+                App::new(Templates::new("templates")),
+            ))
+            .strict(true),
+        ..Config::default()
+    });
+
+    assert_eq!("HTTP/1.1 200 OK", get_host(&server, "hello.test", "/").0);
+    assert_eq!(
+        "HTTP/1.1 421 MISDIRECTED REQUEST",
+        get_host(&server, "evil.test", "/").0
+    );
+}